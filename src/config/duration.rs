@@ -0,0 +1,156 @@
+use std::fmt;
+
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
+use serde::de::{self, Deserializer, Visitor};
+
+// Durations are expressed in seconds, either as a plain integer or as a
+// human-friendly string combining units (`30s`, `2m`, `1h30m`).
+pub fn deserialize<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+  D: Deserializer<'de>,
+{
+  deserializer.deserialize_any(DurationVisitor)
+}
+
+// Same as `deserialize`, for optional fields which must also be marked with
+// `#[serde(default)]`.
+pub fn deserialize_option<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+  D: Deserializer<'de>,
+{
+  deserialize(deserializer).map(Some)
+}
+
+fn parse(value: &str) -> Result<u64, String> {
+  let mut total = 0;
+  let mut number = String::new();
+  let too_large = || format!("invalid duration '{}', duration too large", value);
+
+  for c in value.trim().chars() {
+    if c.is_ascii_digit() {
+      number.push(c);
+      continue;
+    }
+
+    let multiplier = match c {
+      's' => 1,
+      'm' => 60,
+      'h' => 3600,
+      'd' => 86400,
+      _ => return Err(format!("invalid duration '{}', unknown unit '{}'", value, c)),
+    };
+
+    if number.is_empty() {
+      return Err(format!("invalid duration '{}'", value));
+    }
+
+    total = add(total, &number, multiplier).ok_or_else(too_large)?;
+    number.clear();
+  }
+
+  if !number.is_empty() {
+    total = add(total, &number, 1).ok_or_else(too_large)?;
+  } else if value.trim().is_empty() {
+    return Err("empty duration".to_owned());
+  }
+
+  Ok(total)
+}
+
+// Add a number of units of `multiplier` seconds to `total`. Numbers only hold
+// digits, so they can only fail to parse when too large.
+fn add(total: u64, number: &str, multiplier: u64) -> Option<u64> {
+  number.parse::<u64>().ok()?.checked_mul(multiplier)?.checked_add(total)
+}
+
+struct DurationVisitor;
+
+impl<'de> Visitor<'de> for DurationVisitor {
+  type Value = u64;
+
+  fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "a number of seconds or a duration such as '30s', '2m' or '1h'")
+  }
+
+  fn visit_u64<E: de::Error>(self, value: u64) -> Result<u64, E> {
+    Ok(value)
+  }
+
+  fn visit_i64<E: de::Error>(self, value: i64) -> Result<u64, E> {
+    if value < 0 {
+      return Err(E::custom("duration cannot be negative"));
+    }
+
+    Ok(value as u64)
+  }
+
+  fn visit_str<E: de::Error>(self, value: &str) -> Result<u64, E> {
+    parse(value).map_err(E::custom)
+  }
+}
+
+// Schema of a duration field, as accepted by `deserialize`.
+pub struct HumanDuration;
+
+impl JsonSchema for HumanDuration {
+  fn schema_name() -> String {
+    "Duration".to_owned()
+  }
+
+  fn json_schema(_: &mut SchemaGenerator) -> Schema {
+    serde_json::from_value(serde_json::json!({
+      "description": "Number of seconds, or a duration such as '30s', '2m' or '1h30m'",
+      "anyOf": [
+        { "type": "integer", "minimum": 0 },
+        { "type": "string", "pattern": "^([0-9]+[smhd]?)+$" }
+      ]
+    }))
+    .unwrap()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_seconds_and_units() {
+    assert_eq!(parse("30"), Ok(30));
+    assert_eq!(parse("30s"), Ok(30));
+    assert_eq!(parse("2m"), Ok(120));
+    assert_eq!(parse("1h30m"), Ok(5400));
+    assert_eq!(parse("1d2h3m4s5"), Ok(93789));
+    assert_eq!(parse("0s"), Ok(0));
+  }
+
+  #[test]
+  fn ignores_surrounding_whitespace() {
+    assert_eq!(parse(" 1h30m\t"), Ok(5400));
+    assert_eq!(parse("1h 30m"), Err("invalid duration '1h 30m', unknown unit ' '".to_owned()));
+  }
+
+  #[test]
+  fn rejects_empty_durations() {
+    assert_eq!(parse(""), Err("empty duration".to_owned()));
+    assert_eq!(parse("   "), Err("empty duration".to_owned()));
+  }
+
+  #[test]
+  fn rejects_unknown_units_and_missing_numbers() {
+    assert_eq!(parse("5w"), Err("invalid duration '5w', unknown unit 'w'".to_owned()));
+    assert_eq!(parse("-5s"), Err("invalid duration '-5s', unknown unit '-'".to_owned()));
+    assert_eq!(parse("1.5h"), Err("invalid duration '1.5h', unknown unit '.'".to_owned()));
+    assert_eq!(parse("m"), Err("invalid duration 'm'".to_owned()));
+    assert_eq!(parse("1hm"), Err("invalid duration '1hm'".to_owned()));
+  }
+
+  #[test]
+  fn rejects_overflowing_durations() {
+    assert_eq!(parse("18446744073709551615"), Ok(u64::MAX));
+    assert_eq!(parse("18446744073709551616"), Err("invalid duration '18446744073709551616', duration too large".to_owned()));
+    assert_eq!(parse("213503982334602d"), Err("invalid duration '213503982334602d', duration too large".to_owned()));
+    assert_eq!(parse("18446744073709551615s1s"), Err("invalid duration '18446744073709551615s1s', duration too large".to_owned()));
+  }
+}
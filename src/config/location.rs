@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::Marker;
+
+// Position of every node of a configuration file, indexed by its dotted path
+// in the document (`schedulers.0.args.to`). JSON files are parsed as YAML to
+// record their positions, TOML files are not indexed.
+#[derive(Debug, Default)]
+pub struct Source {
+  pub path: PathBuf,
+  marks: HashMap<String, (usize, usize)>,
+}
+
+impl Source {
+  pub fn parse(path: &Path, content: &str) -> Source {
+    let mut recorder = Recorder::default();
+
+    if path.extension().and_then(|ext| ext.to_str()) != Some("toml") {
+      let _ = Parser::new(content.chars()).load(&mut recorder, false);
+    }
+
+    Source {
+      path: path.to_owned(),
+      marks: recorder.marks,
+    }
+  }
+
+  pub fn at(&self, line: usize, column: usize) -> Location {
+    Location {
+      file: self.path.clone(),
+      line,
+      column,
+    }
+  }
+
+  pub fn at_offset(&self, content: &str, offset: usize) -> Location {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.chars().rev().take_while(|c| *c != '\n').count() + 1;
+
+    self.at(line, column)
+  }
+
+  // Errors raised while deserializing tagged items are located at the start of
+  // the item, this finds the position of `field` within the item at (line, column).
+  pub fn find_field(&self, line: usize, column: usize, field: &str) -> Option<Location> {
+    let parent = self.marks.iter().filter(|(_, mark)| **mark == (line, column)).map(|(path, _)| path).min_by_key(|path| path.len())?;
+    let suffix = format!(".{}", field);
+
+    self
+      .marks
+      .iter()
+      .filter(|(path, _)| path.starts_with(parent.as_str()) && path.ends_with(&suffix))
+      .min_by_key(|(path, _)| path.len())
+      .map(|(_, (line, column))| self.at(*line, *column))
+  }
+
+  // Find the position of the node at `path`, or of its closest recorded parent.
+  fn locate(&self, path: &str) -> Location {
+    let mut path = path;
+
+    loop {
+      if let Some((line, column)) = self.marks.get(path) {
+        return self.at(*line, *column);
+      }
+
+      match path.rfind('.') {
+        Some(idx) => path = &path[..idx],
+        None if !path.is_empty() => path = "",
+        None => return self.at(0, 0),
+      }
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct Location {
+  pub file: PathBuf,
+  pub line: usize,
+  pub column: usize,
+}
+
+impl fmt::Display for Location {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    if self.line == 0 {
+      write!(f, "{}", self.file.display())
+    } else {
+      write!(f, "{}:{}:{}", self.file.display(), self.line, self.column)
+    }
+  }
+}
+
+// Where a configuration item was defined, kept on each item so errors can be
+// reported against the right file after includes are merged.
+#[derive(Debug, Clone, Default)]
+pub struct Origin {
+  source: Option<Arc<Source>>,
+  path: String,
+}
+
+impl Origin {
+  pub fn new(source: &Arc<Source>, path: String) -> Origin {
+    Origin {
+      source: Some(Arc::clone(source)),
+      path,
+    }
+  }
+
+  // Whether the attribute is set in the item, as far as the source tells.
+  pub fn defines(&self, attribute: &str) -> bool {
+    self.source.as_ref().is_some_and(|source| source.marks.contains_key(&format!("{}.{}", self.path, attribute)))
+  }
+
+  pub fn locate(&self, attribute: &str) -> Option<Location> {
+    let path = if attribute.is_empty() { self.path.clone() } else { format!("{}.{}", self.path, attribute) };
+
+    self.source.as_ref().map(|source| source.locate(&path))
+  }
+}
+
+enum Frame {
+  Mapping(Option<String>),
+  Sequence(usize),
+}
+
+#[derive(Default)]
+struct Recorder {
+  frames: Vec<Frame>,
+  path: Vec<String>,
+  marks: HashMap<String, (usize, usize)>,
+}
+
+impl Recorder {
+  fn enter(&mut self, mark: Marker) {
+    match self.frames.last() {
+      Some(Frame::Mapping(Some(key))) => self.path.push(key.to_owned()),
+      Some(Frame::Sequence(idx)) => self.path.push(idx.to_string()),
+      _ => {}
+    }
+
+    self.marks.insert(self.path.join("."), (mark.line(), mark.col() + 1));
+  }
+
+  fn leave(&mut self) {
+    match self.frames.last_mut() {
+      Some(Frame::Mapping(key)) => {
+        *key = None;
+        self.path.pop();
+      }
+
+      Some(Frame::Sequence(idx)) => {
+        *idx += 1;
+        self.path.pop();
+      }
+
+      None => {}
+    }
+  }
+}
+
+impl MarkedEventReceiver for Recorder {
+  fn on_event(&mut self, event: Event, mark: Marker) {
+    match event {
+      Event::Scalar(value, ..) => {
+        if let Some(Frame::Mapping(key @ None)) = self.frames.last_mut() {
+          *key = Some(value);
+          return;
+        }
+
+        self.enter(mark);
+        self.leave();
+      }
+
+      Event::Alias(_) => {
+        self.enter(mark);
+        self.leave();
+      }
+
+      Event::MappingStart(_) => {
+        self.enter(mark);
+        self.frames.push(Frame::Mapping(None));
+      }
+
+      Event::SequenceStart(_) => {
+        self.enter(mark);
+        self.frames.push(Frame::Sequence(0));
+      }
+
+      Event::MappingEnd | Event::SequenceEnd => {
+        self.frames.pop();
+        self.leave();
+      }
+
+      _ => {}
+    }
+  }
+}
@@ -0,0 +1,1633 @@
+extern crate serde_yaml;
+
+mod duration;
+mod location;
+mod validation;
+
+use regex::Regex;
+use reqwest::tls::{Certificate, Identity, Version};
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize};
+use std::sync::{Arc, Mutex, OnceLock};
+use tonic::transport::ClientTlsConfig;
+
+use self::location::{Origin, Source};
+pub use self::validation::{ConfigError, ConfigErrors};
+
+use crate::datasource::{self, DataSource};
+use crate::interface::specs::{self, HttpMethod::*};
+use crate::scheduler::{strategies, *};
+use crate::template::{JsonTemplate, Template};
+use crate::util;
+
+// Tokens are renewed this many seconds before they expire, unless configured.
+const REFRESH_BEFORE: u64 = 30;
+
+// Parts of a request signed with HMAC, unless configured.
+const DEFAULT_COMPONENTS: &[&str] = &["method", "path", "date", "body"];
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+  #[serde(default)]
+  include: Vec<String>,
+  options: Option<ConfigOptions>,
+  #[serde(default)]
+  schedulers: Vec<ConfigScheduler>,
+  #[serde(default)]
+  defaults: Option<ConfigUpstream>,
+  #[serde(default)]
+  templates: HashMap<String, ConfigUpstream>,
+  #[serde(default)]
+  upstreams: Vec<ConfigUpstream>,
+  datasources: Option<HashMap<String, ConfigDatasource>>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigOptions {
+  // Options of every merged file, the last one taking precedence
+  #[serde(skip)]
+  origins: Vec<Origin>,
+  pub timeout: Option<u64>,
+  pub selection: Option<ConfigSelection>,
+  pub cookies: Option<bool>,
+  pub virtual_users: Option<u64>,
+  pub tls: Option<ConfigTls>,
+  pub connections: Option<ConfigConnections>,
+  #[serde(default)]
+  pub resolve: HashMap<String, String>,
+  pub proxy: Option<ConfigProxy>,
+  pub bind_address: Option<ConfigBindAddress>,
+}
+
+// Proxy given by its URL, with optional credentials and hosts reached directly.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum ConfigProxy {
+  Url(String),
+  Options(ConfigProxyOptions),
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigProxyOptions {
+  url: String,
+  username: Option<String>,
+  password: Option<String>,
+  #[serde(default)]
+  no_proxy: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum ConfigBindAddress {
+  One(String),
+  Many(Vec<String>),
+}
+
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigConnections {
+  reuse: Option<bool>,
+  pool_size: Option<usize>,
+  max_requests: Option<u64>,
+  #[serde(default, deserialize_with = "duration::deserialize_option")]
+  #[schemars(with = "Option<duration::HumanDuration>")]
+  idle_timeout: Option<u64>,
+}
+
+// TLS settings of the scenario, each of them can be overridden by upstreams.
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigTls {
+  ca: Option<String>,
+  cert: Option<String>,
+  key: Option<String>,
+  pkcs12: Option<String>,
+  password: Option<String>,
+  insecure_skip_verify: Option<bool>,
+  sni: Option<String>,
+  min_version: Option<ConfigTlsVersion>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, JsonSchema)]
+enum ConfigTlsVersion {
+  #[serde(rename = "1.0")]
+  Tls10,
+  #[serde(rename = "1.1")]
+  Tls11,
+  #[serde(rename = "1.2")]
+  Tls12,
+  #[serde(rename = "1.3")]
+  Tls13,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigSelection {
+  Random,
+  RoundRobin,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(from = "ConfigSchedulerKind")]
+struct ConfigScheduler {
+  origin: Origin,
+  kind: ConfigSchedulerKind,
+}
+
+impl From<ConfigSchedulerKind> for ConfigScheduler {
+  fn from(kind: ConfigSchedulerKind) -> ConfigScheduler {
+    ConfigScheduler { origin: Origin::default(), kind }
+  }
+}
+
+impl JsonSchema for ConfigScheduler {
+  fn schema_name() -> String {
+    ConfigSchedulerKind::schema_name()
+  }
+
+  fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+    ConfigSchedulerKind::json_schema(gen)
+  }
+}
+
+// Each scheduler kind deserializes its own arguments, selected by `kind`.
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(tag = "kind", deny_unknown_fields)]
+enum ConfigSchedulerKind {
+  Constant {
+    args: ConfigConstant,
+    #[serde(default)]
+    upstreams: Vec<String>,
+    #[serde(default)]
+    weights: HashMap<String, f64>,
+    selection: Option<ConfigSelection>,
+  },
+  SteppedConstant {
+    steps: Vec<ConfigStep>,
+    #[serde(default)]
+    upstreams: Vec<String>,
+    #[serde(default)]
+    weights: HashMap<String, f64>,
+    selection: Option<ConfigSelection>,
+  },
+  DoubleEvery {
+    args: ConfigDoubleEvery,
+    #[serde(default)]
+    upstreams: Vec<String>,
+    #[serde(default)]
+    weights: HashMap<String, f64>,
+    selection: Option<ConfigSelection>,
+  },
+  RampUp {
+    args: ConfigRampUp,
+    #[serde(default)]
+    upstreams: Vec<String>,
+    #[serde(default)]
+    weights: HashMap<String, f64>,
+    selection: Option<ConfigSelection>,
+  },
+  Pause {
+    args: ConfigPause,
+  },
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct ConfigConstant {
+  #[serde(deserialize_with = "duration::deserialize")]
+  #[schemars(with = "duration::HumanDuration")]
+  duration: u64,
+  count: f64,
+  #[serde(deserialize_with = "duration::deserialize")]
+  #[schemars(with = "duration::HumanDuration")]
+  interval: u64,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct ConfigStep {
+  #[serde(deserialize_with = "duration::deserialize")]
+  #[schemars(with = "duration::HumanDuration")]
+  duration: u64,
+  count: f64,
+  #[serde(deserialize_with = "duration::deserialize")]
+  #[schemars(with = "duration::HumanDuration")]
+  interval: u64,
+  #[serde(default)]
+  upstreams: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct ConfigDoubleEvery {
+  #[serde(deserialize_with = "duration::deserialize")]
+  #[schemars(with = "duration::HumanDuration")]
+  duration: u64,
+  #[serde(deserialize_with = "duration::deserialize")]
+  #[schemars(with = "duration::HumanDuration")]
+  period: u64,
+  count: f64,
+  #[serde(deserialize_with = "duration::deserialize")]
+  #[schemars(with = "duration::HumanDuration")]
+  interval: u64,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct ConfigRampUp {
+  #[serde(deserialize_with = "duration::deserialize")]
+  #[schemars(with = "duration::HumanDuration")]
+  duration: u64,
+  #[serde(deserialize_with = "duration::deserialize")]
+  #[schemars(with = "duration::HumanDuration")]
+  interval: u64,
+  from: f64,
+  to: f64,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct ConfigPause {
+  #[serde(deserialize_with = "duration::deserialize")]
+  #[schemars(with = "duration::HumanDuration")]
+  duration: u64,
+}
+
+// Upstreams, templates and defaults share the same structure, every field
+// left empty is inherited from the parent (through `extends`) then from `defaults`.
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct ConfigUpstream {
+  #[serde(skip)]
+  origin: Origin,
+  #[serde(default)]
+  name: String,
+  #[serde(default)]
+  kind: Option<ConfigUpstreamKind>,
+  #[serde(default)]
+  extends: Option<String>,
+  #[serde(default)]
+  base_url: Option<String>,
+  #[serde(default)]
+  method: Option<String>,
+  #[serde(default)]
+  weight: Option<f64>,
+  #[serde(default)]
+  url: Option<String>,
+  #[serde(default)]
+  headers: HashMap<String, String>,
+  #[serde(default)]
+  basic: Option<ConfigUpstreamBasicAuth>,
+  #[serde(default)]
+  auth: Option<ConfigAuth>,
+  #[serde(default)]
+  signing: Option<ConfigSigning>,
+  #[serde(default)]
+  tls: Option<ConfigTls>,
+  #[serde(default)]
+  protocol: Option<ConfigProtocol>,
+  #[serde(default)]
+  resolve: HashMap<String, String>,
+  #[serde(default)]
+  proxy: Option<ConfigProxy>,
+  #[serde(default)]
+  bind_address: Option<ConfigBindAddress>,
+  #[serde(default)]
+  follow_redirects: Option<bool>,
+  #[serde(default)]
+  max_redirects: Option<usize>,
+  #[serde(default)]
+  decompress: Option<ConfigDecompress>,
+  #[serde(default)]
+  read_body: Option<ConfigReadBody>,
+  #[serde(default)]
+  websocket: Option<ConfigWebsocket>,
+  #[serde(default)]
+  grpc: Option<ConfigGrpc>,
+  #[serde(default)]
+  socket: Option<ConfigSocket>,
+  #[serde(default)]
+  body: Option<String>,
+  #[serde(default)]
+  body_file: Option<ConfigBodyFile>,
+  #[serde(default)]
+  form: Option<HashMap<String, String>>,
+  #[serde(default)]
+  multipart: Option<HashMap<String, ConfigPart>>,
+  #[serde(default)]
+  json: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum ConfigUpstreamKind {
+  Http,
+  Websocket,
+  Grpc,
+  Tcp,
+  Udp,
+}
+
+// Messages sent on the connections opened by a `websocket` upstream.
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct ConfigWebsocket {
+  #[serde(default)]
+  messages: Vec<ConfigMessage>,
+  #[serde(default, deserialize_with = "duration::deserialize_option")]
+  #[schemars(with = "Option<duration::HumanDuration>")]
+  interval: Option<u64>,
+  repeat: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct ConfigMessage {
+  send: String,
+  expect: Option<String>,
+  #[serde(default, deserialize_with = "duration::deserialize_option")]
+  #[schemars(with = "Option<duration::HumanDuration>")]
+  timeout: Option<u64>,
+}
+
+// Method called by a `grpc` upstream, described by a proto file or by the
+// server through reflection.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct ConfigGrpc {
+  method: String,
+  proto: Option<String>,
+  #[serde(default)]
+  includes: Vec<String>,
+  #[serde(default)]
+  reflection: bool,
+}
+
+// Payload sent by `tcp` and `udp` upstreams, and how much of their reply is
+// read, until a delimiter or a number of bytes.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct ConfigSocket {
+  send: String,
+  #[serde(default)]
+  hex: bool,
+  read_until: Option<String>,
+  read_bytes: Option<u64>,
+  expect: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct ConfigUpstreamBasicAuth {
+  username: String,
+  password: String,
+}
+
+// Token sent in the `Authorization` header, either given as is or obtained
+// from a token endpoint or a login upstream, selected by `kind`.
+#[derive(Debug, Clone, PartialEq, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+enum ConfigAuth {
+  Bearer {
+    token: String,
+  },
+  Oauth2 {
+    token_url: String,
+    client_id: String,
+    client_secret: String,
+    scope: Option<String>,
+    #[serde(default, deserialize_with = "duration::deserialize_option")]
+    #[schemars(with = "Option<duration::HumanDuration>")]
+    refresh_before: Option<u64>,
+  },
+  Login {
+    upstream: String,
+    token: String,
+    expires_in: Option<String>,
+    #[serde(default, deserialize_with = "duration::deserialize_option")]
+    #[schemars(with = "Option<duration::HumanDuration>")]
+    ttl: Option<u64>,
+    #[serde(default, deserialize_with = "duration::deserialize_option")]
+    #[schemars(with = "Option<duration::HumanDuration>")]
+    refresh_before: Option<u64>,
+  },
+}
+
+// Either every supported encoding, or some of them.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum ConfigDecompress {
+  All(bool),
+  Encodings(Vec<ConfigCompression>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum ConfigCompression {
+  Gzip,
+  Brotli,
+  Deflate,
+}
+
+// Response bodies are either discarded, read fully, or read up to a number of
+// bytes.
+#[derive(Debug, Clone, Copy, Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum ConfigReadBody {
+  Mode(ConfigReadBodyMode),
+  Bytes(u64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum ConfigReadBodyMode {
+  Discard,
+  Full,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum ConfigProtocol {
+  Http1,
+  H2c,
+  H2,
+  Http3,
+}
+
+// Signature computed over the final request, selected by `kind`.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+enum ConfigSigning {
+  Hmac {
+    secret: String,
+    #[serde(default)]
+    algorithm: ConfigHmacAlgorithm,
+    header: Option<String>,
+    prefix: Option<String>,
+    #[serde(default)]
+    encoding: ConfigEncoding,
+    components: Option<Vec<String>>,
+  },
+  AwsSigv4 {
+    access_key: String,
+    secret_key: String,
+    region: String,
+    service: Option<String>,
+    session_token: Option<String>,
+  },
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum ConfigHmacAlgorithm {
+  #[default]
+  Sha256,
+  Sha512,
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum ConfigEncoding {
+  #[default]
+  Hex,
+  Base64,
+}
+
+// File sent as the body, as is or as a template.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum ConfigBodyFile {
+  Path(String),
+  Options(ConfigBodyFileOptions),
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct ConfigBodyFileOptions {
+  path: String,
+  #[serde(default)]
+  templated: bool,
+}
+
+impl ConfigTls {
+  // Fill every unset setting from `parent`. A client certificate is taken as a
+  // whole, whatever its format.
+  fn merge(tls: Option<ConfigTls>, parent: Option<&ConfigTls>) -> Option<ConfigTls> {
+    let parent = match parent {
+      None => return tls,
+      Some(parent) => parent.clone(),
+    };
+
+    let tls = match tls {
+      None => return Some(parent),
+      Some(tls) => tls,
+    };
+
+    let identity = match tls.cert.is_some() || tls.pkcs12.is_some() {
+      true => (tls.cert, tls.key, tls.pkcs12, tls.password),
+      false => (parent.cert, parent.key, parent.pkcs12, parent.password),
+    };
+
+    Some(ConfigTls {
+      ca: tls.ca.or(parent.ca),
+      cert: identity.0,
+      key: identity.1,
+      pkcs12: identity.2,
+      password: identity.3,
+      insecure_skip_verify: tls.insecure_skip_verify.or(parent.insecure_skip_verify),
+      sni: tls.sni.or(parent.sni),
+      min_version: tls.min_version.or(parent.min_version),
+    })
+  }
+
+  // Certificates are loaded once, when the scenario is created.
+  fn create(&self) -> Result<specs::Tls, String> {
+    let read = |path: &str| fs::read(path).map_err(|err| format!("could not read '{}': {}", path, err));
+    let reason = |err: reqwest::Error| err.source().map_or_else(|| err.to_string(), ToString::to_string);
+
+    let ca = match self.ca {
+      None => vec![],
+      Some(ref path) => Certificate::from_pem_bundle(&read(path)?).map_err(|err| format!("invalid CA bundle '{}': {}", path, reason(err)))?,
+    };
+
+    let identity = match (&self.cert, &self.key, &self.pkcs12) {
+      (Some(cert), key, _) => {
+        let mut pem = read(cert)?;
+
+        if let Some(key) = key {
+          pem.push(b'\n');
+          pem.extend(read(key)?);
+        }
+
+        Some(Identity::from_pem(&pem).map_err(|err| format!("invalid client certificate '{}': {}", cert, reason(err)))?)
+      }
+
+      (None, _, Some(pkcs12)) => {
+        Some(Identity::from_pkcs12_der(&read(pkcs12)?, self.password.as_deref().unwrap_or_default()).map_err(|err| format!("invalid client certificate '{}': {}", pkcs12, reason(err)))?)
+      }
+
+      _ => None,
+    };
+
+    Ok(specs::Tls {
+      ca,
+      // PEM certificates and TLS 1.3 are only supported by rustls, PKCS#12
+      // certificates and TLS 1.0 and 1.1 by the native TLS library
+      rustls: self.cert.is_some() || self.min_version == Some(ConfigTlsVersion::Tls13),
+      identity,
+      insecure_skip_verify: self.insecure_skip_verify.unwrap_or(false),
+      sni: self.sni.clone(),
+      min_version: self.min_version.map(|version| match version {
+        ConfigTlsVersion::Tls10 => Version::TLS_1_0,
+        ConfigTlsVersion::Tls11 => Version::TLS_1_1,
+        ConfigTlsVersion::Tls12 => Version::TLS_1_2,
+        ConfigTlsVersion::Tls13 => Version::TLS_1_3,
+      }),
+    })
+  }
+}
+
+impl ConfigTls {
+  // Settings of the connectors of WebSocket and gRPC upstreams.
+  fn settings(&self) -> tls::Settings {
+    tls::Settings {
+      ca: self.ca.clone(),
+      cert: self.cert.clone(),
+      key: self.key.clone(),
+      pkcs12: self.pkcs12.clone(),
+      password: self.password.clone(),
+      insecure_skip_verify: self.insecure_skip_verify.unwrap_or(false),
+      sni: self.sni.clone(),
+      min_version: self.min_version.map(|version| match version {
+        ConfigTlsVersion::Tls10 => native_tls::Protocol::Tlsv10,
+        ConfigTlsVersion::Tls11 => native_tls::Protocol::Tlsv11,
+        ConfigTlsVersion::Tls12 => native_tls::Protocol::Tlsv12,
+        ConfigTlsVersion::Tls13 => native_tls::Protocol::Tlsv13,
+      }),
+    }
+  }
+}
+
+impl ConfigGrpc {
+  // Methods are named `package.Service/Method`, or `package.Service.Method`.
+  fn names(&self) -> Option<(&str, &str)> {
+    let method = self.method.trim_start_matches('/');
+
+    method
+      .rsplit_once('/')
+      .or_else(|| method.rsplit_once('.'))
+      .filter(|(service, method)| !service.is_empty() && !method.is_empty())
+  }
+
+  fn load(&self, proto: &str) -> Result<prost_reflect::MethodDescriptor, String> {
+    let (service, method) = self.names().ok_or_else(|| format!("invalid method '{}'", self.method))?;
+
+    grpc::load(proto, &self.includes, service, method)
+  }
+
+  fn create(&self, tls: Option<ClientTlsConfig>, descriptor: Option<prost_reflect::MethodDescriptor>) -> Result<specs::Grpc, String> {
+    let (service, method) = self.names().unwrap_or_default();
+    let path = format!("/{}/{}", service, method).parse().map_err(|_| format!("invalid method '{}'", self.method))?;
+
+    Ok(specs::Grpc {
+      service: service.to_owned(),
+      method: method.to_owned(),
+      path,
+      proto: self.proto.clone(),
+      descriptor: Mutex::new(descriptor),
+      tls,
+      runtime: OnceLock::new(),
+      channels: Mutex::default(),
+    })
+  }
+}
+
+impl ConfigSocket {
+  fn create(&self, transport: specs::Transport) -> specs::Socket {
+    specs::Socket {
+      transport,
+      payload: compile(&self.send),
+      hex: self.hex,
+      reply: match (&self.read_until, self.read_bytes) {
+        (Some(delimiter), _) => specs::Reply::Delimiter(delimiter.as_bytes().to_vec()),
+        (None, Some(bytes)) => specs::Reply::Bytes(bytes),
+        (None, None) => specs::Reply::Ignore,
+      },
+      expect: self.expect.as_ref().and_then(|expect| regex::bytes::Regex::new(expect).ok()),
+    }
+  }
+}
+
+impl ConfigWebsocket {
+  // Replies are awaited for the request timeout, unless set for a message.
+  fn create(&self, timeout: u64, tls: Option<native_tls::TlsConnector>, sni: Option<String>) -> specs::Websocket {
+    specs::Websocket {
+      messages: self
+        .messages
+        .iter()
+        .map(|message| specs::Message {
+          send: compile(&message.send),
+          expect: message.expect.as_ref().and_then(|expect| Regex::new(expect).ok()),
+          timeout: message.timeout.unwrap_or(timeout),
+        })
+        .collect(),
+      interval: self.interval.unwrap_or(0),
+      repeat: self.repeat.unwrap_or(1),
+      tls,
+      sni,
+    }
+  }
+}
+
+impl ConfigProxy {
+  fn options(&self) -> ConfigProxyOptions {
+    match self {
+      ConfigProxy::Url(url) => ConfigProxyOptions {
+        url: url.to_owned(),
+        username: None,
+        password: None,
+        no_proxy: vec![],
+      },
+      ConfigProxy::Options(options) => options.clone(),
+    }
+  }
+
+  fn create(&self) -> specs::Proxy {
+    let options = self.options();
+
+    specs::Proxy {
+      url: options.url,
+      username: options.username,
+      password: options.password,
+      no_proxy: options.no_proxy,
+    }
+  }
+}
+
+impl ConfigBindAddress {
+  fn addresses(&self) -> Vec<String> {
+    match self {
+      ConfigBindAddress::One(addr) => vec![addr.to_owned()],
+      ConfigBindAddress::Many(addrs) => addrs.clone(),
+    }
+  }
+}
+
+impl ConfigSigning {
+  fn create(&self) -> specs::Signing {
+    match self.clone() {
+      ConfigSigning::Hmac {
+        secret,
+        algorithm,
+        header,
+        prefix,
+        encoding,
+        components,
+      } => specs::Signing::Hmac {
+        secret,
+        algorithm: match algorithm {
+          ConfigHmacAlgorithm::Sha256 => specs::HmacAlgorithm::Sha256,
+          ConfigHmacAlgorithm::Sha512 => specs::HmacAlgorithm::Sha512,
+        },
+        header: header.unwrap_or_else(|| "X-Signature".to_owned()),
+        prefix: prefix.unwrap_or_default(),
+        base64: matches!(encoding, ConfigEncoding::Base64),
+        components: components
+          .unwrap_or_else(|| DEFAULT_COMPONENTS.iter().map(|component| component.to_string()).collect())
+          .iter()
+          .filter_map(|component| specs::Component::parse(component))
+          .collect(),
+      },
+
+      ConfigSigning::AwsSigv4 {
+        access_key,
+        secret_key,
+        region,
+        service,
+        session_token,
+      } => specs::Signing::AwsSigv4 {
+        access_key,
+        secret_key,
+        region,
+        service: service.unwrap_or_else(|| "s3".to_owned()),
+        session_token,
+      },
+    }
+  }
+}
+
+impl ConfigBodyFile {
+  fn path(&self) -> &str {
+    match self {
+      ConfigBodyFile::Path(path) => path,
+      ConfigBodyFile::Options(options) => &options.path,
+    }
+  }
+
+  fn templated(&self) -> bool {
+    match self {
+      ConfigBodyFile::Path(_) => false,
+      ConfigBodyFile::Options(options) => options.templated,
+    }
+  }
+}
+
+// A multipart field is either a value or a file.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum ConfigPart {
+  Text(String),
+  File(ConfigFilePart),
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct ConfigFilePart {
+  file: String,
+  filename: Option<String>,
+  content_type: Option<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct ConfigDatasource {
+  #[serde(skip)]
+  origin: Origin,
+  kind: String,
+  source: Option<String>,
+  data: Option<Vec<String>>,
+  delimiter: Option<char>,
+  glob: Option<String>,
+  #[serde(default)]
+  whole_files: bool,
+  #[serde(default)]
+  binary: bool,
+  #[serde(default)]
+  mode: ConfigDatasourceMode,
+  on_exhausted: Option<ConfigExhausted>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum ConfigDatasourceMode {
+  #[default]
+  Random,
+  Sequential,
+  Once,
+  Shuffle,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum ConfigExhausted {
+  Fail,
+  Stop,
+}
+
+impl Config {
+  pub fn read(file: &str) -> Result<Config, Box<dyn Error>> {
+    Config::read_included(Path::new(file), &mut vec![], &mut HashSet::new())
+  }
+
+  // Scenario described by the YAML content of a scenario file.
+  #[cfg(test)]
+  pub fn scenario(content: &str) -> specs::Scenario {
+    let source = Arc::new(Source::parse(Path::new("scenario.yml"), content));
+    let mut config = Config::parse(&source, content).unwrap();
+
+    config.set_origins(&source);
+    config.create_scenario().unwrap()
+  }
+
+  // Read a configuration file and recursively merge all the files it includes,
+  // `stack` holds the files currently being read to detect circular includes.
+  // Files included more than once, such as a library shared by two included
+  // files, are only merged the first time.
+  fn read_included(path: &Path, stack: &mut Vec<PathBuf>, merged: &mut HashSet<PathBuf>) -> Result<Config, Box<dyn Error>> {
+    let canonical = path.canonicalize().map_err(|err| format!("could not read '{}': {}", path.display(), err))?;
+
+    if stack.contains(&canonical) {
+      return Err(format!("circular include of '{}'", path.display()).into());
+    }
+
+    if !merged.insert(canonical.clone()) {
+      return Ok(Config::empty());
+    }
+
+    let mut content = String::new();
+    let mut file = File::open(path).map_err(|err| format!("could not read '{}': {}", path.display(), err))?;
+
+    file.read_to_string(&mut content)?;
+
+    let source = Arc::new(Source::parse(path, &content));
+    let mut config = Config::parse(&source, &content)?;
+
+    config.set_origins(&source);
+
+    if config.include.is_empty() {
+      return Ok(config);
+    }
+
+    stack.push(canonical);
+
+    let base = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut result = Config::empty();
+
+    for include in &config.include {
+      let included = Config::read_included(&base.join(include), stack, merged)?;
+
+      result.merge(included);
+    }
+
+    stack.pop();
+    result.merge(config);
+
+    Ok(result)
+  }
+
+  // The scenario format is detected from the file extension, defaulting to YAML.
+  fn parse(source: &Source, content: &str) -> Result<Config, ConfigError> {
+    match source.path.extension().and_then(|ext| ext.to_str()) {
+      Some("json") => serde_json::from_str(content).map_err(|err| ConfigError {
+        location: Some(source.at(err.line(), err.column())),
+        message: strip_position(&err.to_string()),
+      }),
+
+      Some("toml") => toml::from_str(content).map_err(|err| ConfigError {
+        location: err.span().map(|span| source.at_offset(content, span.start)),
+        message: err.message().to_owned(),
+      }),
+
+      _ => serde_yaml::from_str(content).map_err(|err| parse_error(source, &err)),
+    }
+  }
+
+  // JSON Schema describing the scenario format.
+  pub fn schema() -> String {
+    serde_json::to_string_pretty(&schemars::schema_for!(Config)).unwrap()
+  }
+
+  // Record where each item was defined, for error reporting.
+  fn set_origins(&mut self, source: &Arc<Source>) {
+    if let Some(ref mut options) = self.options {
+      options.origins = vec![Origin::new(source, "options".to_owned())];
+    }
+
+    for (idx, scheduler) in self.schedulers.iter_mut().enumerate() {
+      scheduler.origin = Origin::new(source, format!("schedulers.{}", idx));
+    }
+
+    for (idx, upstream) in self.upstreams.iter_mut().enumerate() {
+      upstream.origin = Origin::new(source, format!("upstreams.{}", idx));
+    }
+
+    for (name, template) in self.templates.iter_mut() {
+      template.origin = Origin::new(source, format!("templates.{}", name));
+    }
+
+    if let Some(ref mut defaults) = self.defaults {
+      defaults.origin = Origin::new(source, "defaults".to_owned());
+    }
+
+    if let Some(ref mut datasources) = self.datasources {
+      for (name, datasource) in datasources.iter_mut() {
+        datasource.origin = Origin::new(source, format!("datasources.{}", name));
+      }
+    }
+  }
+
+  fn empty() -> Config {
+    Config {
+      include: vec![],
+      options: None,
+      schedulers: vec![],
+      defaults: None,
+      templates: HashMap::new(),
+      upstreams: vec![],
+      datasources: None,
+    }
+  }
+
+  // Merge another configuration on top of this one, schedulers and upstreams are
+  // appended while named items and options from `other` take precedence.
+  fn merge(&mut self, other: Config) {
+    if let Some(options) = other.options {
+      self.options = match self.options.take() {
+        None => Some(options),
+        Some(base) => Some(ConfigOptions {
+          origins: base.origins.into_iter().chain(options.origins).collect(),
+          timeout: options.timeout.or(base.timeout),
+          selection: options.selection.or(base.selection),
+          cookies: options.cookies.or(base.cookies),
+          virtual_users: options.virtual_users.or(base.virtual_users),
+          tls: ConfigTls::merge(options.tls, base.tls.as_ref()),
+          connections: options.connections.or(base.connections),
+          resolve: base.resolve.into_iter().chain(options.resolve).collect(),
+          proxy: options.proxy.or(base.proxy),
+          bind_address: options.bind_address.or(base.bind_address),
+        }),
+      };
+    }
+
+    if let Some(defaults) = other.defaults {
+      self.defaults = match self.defaults.take() {
+        None => Some(defaults),
+        Some(base) => Some(defaults.inherit(&base)),
+      };
+    }
+
+    if let Some(datasources) = other.datasources {
+      self.datasources.get_or_insert_with(HashMap::new).extend(datasources);
+    }
+
+    self.schedulers.extend(other.schedulers);
+    self.templates.extend(other.templates);
+    self.upstreams.extend(other.upstreams);
+  }
+
+  // Follow the `extends` chain of an upstream, then apply the global defaults.
+  fn resolve_upstream(&self, upstream: &ConfigUpstream) -> Result<ConfigUpstream, String> {
+    let mut resolved = upstream.clone();
+    let mut seen = HashSet::new();
+    seen.insert(upstream.name.clone());
+
+    while let Some(parent) = resolved.extends.take() {
+      if !seen.insert(parent.clone()) {
+        return Err(format!("upstream '{}' has a circular 'extends' on '{}'", upstream.name, parent));
+      }
+
+      let template = match self.templates.get(&parent) {
+        Some(template) => template,
+        None => match self.upstreams.iter().find(|u| u.name == parent) {
+          Some(template) => template,
+          None => return Err(format!("upstream '{}' extends unknown template '{}'", upstream.name, parent)),
+        },
+      };
+
+      resolved = resolved.inherit(template);
+    }
+
+    if let Some(ref defaults) = self.defaults {
+      resolved = resolved.inherit(defaults);
+    }
+
+    Ok(resolved)
+  }
+
+  // Network settings of an upstream, on top of those of the scenario. Invalid
+  // addresses are reported by the validation.
+  fn network(&self, upstream: &ConfigUpstream) -> specs::Network {
+    let options = self.options.as_ref();
+
+    let mut resolve = options.map(|options| options.resolve.clone()).unwrap_or_default();
+    resolve.extend(upstream.resolve.clone());
+
+    let proxy = upstream.proxy.as_ref().or_else(|| options.and_then(|options| options.proxy.as_ref()));
+    let bind_address = upstream.bind_address.as_ref().or_else(|| options.and_then(|options| options.bind_address.as_ref()));
+
+    let mut resolve = resolve.into_iter().filter_map(|(host, addr)| Some((host, addr.parse().ok()?))).collect::<Vec<_>>();
+    resolve.sort();
+
+    specs::Network {
+      resolve,
+      proxy: proxy.map(ConfigProxy::create),
+      bind: bind_address.map(ConfigBindAddress::addresses).unwrap_or_default().iter().filter_map(|addr| addr.parse().ok()).collect(),
+      next_bind: AtomicUsize::new(0),
+    }
+  }
+
+  // Upstreams using identical authentications share their tokens.
+  fn auth(&self, auth: &ConfigAuth, auths: &mut Vec<(ConfigAuth, Arc<specs::Auth>)>) -> Arc<specs::Auth> {
+    if let Some((_, shared)) = auths.iter().find(|(config, _)| config == auth) {
+      return Arc::clone(shared);
+    }
+
+    let kind = match auth.clone() {
+      ConfigAuth::Bearer { token } => specs::AuthKind::Bearer(token),
+
+      ConfigAuth::Oauth2 {
+        token_url,
+        client_id,
+        client_secret,
+        scope,
+        refresh_before,
+      } => specs::AuthKind::OAuth2 {
+        token_url,
+        client_id,
+        client_secret,
+        scope,
+        refresh_before: refresh_before.unwrap_or(REFRESH_BEFORE),
+      },
+
+      ConfigAuth::Login {
+        upstream,
+        token,
+        expires_in,
+        ttl,
+        refresh_before,
+      } => specs::AuthKind::Login {
+        upstream: self.upstreams.iter().position(|candidate| candidate.name == upstream).unwrap_or_default(),
+        token,
+        expires_in,
+        ttl,
+        refresh_before: refresh_before.unwrap_or(REFRESH_BEFORE),
+      },
+    };
+
+    let shared = Arc::new(specs::Auth::new(kind));
+    auths.push((auth.clone(), Arc::clone(&shared)));
+
+    shared
+  }
+
+  // The selection mode of a scheduler defaults to the one from the scenario options.
+  fn selector(&self, upstreams: &[String], weights: &HashMap<String, f64>, selection: Option<ConfigSelection>) -> Selector {
+    let selection = selection.or_else(|| self.options.as_ref().and_then(|options| options.selection));
+
+    let selection = match selection {
+      Some(ConfigSelection::RoundRobin) => Selection::RoundRobin,
+      _ => Selection::Random,
+    };
+
+    Selector::new(upstreams.to_vec(), weights.clone(), selection)
+  }
+
+  pub fn create_scenario(&self) -> Result<specs::Scenario, ConfigErrors> {
+    let errors = self.validate();
+
+    if !errors.is_empty() {
+      return Err(ConfigErrors(errors));
+    }
+
+    let mut scenario = specs::Scenario {
+      options: specs::Options::default(),
+      start: util::current_epoch_with_ms(),
+      schedulers: vec![],
+      upstreams: vec![],
+      datasources: HashMap::new(),
+      stopped: AtomicBool::new(false),
+      sequence: AtomicU64::new(0),
+      users: vec![],
+      next_user: AtomicUsize::new(0),
+      connections: Arc::default(),
+      distribution: Arc::default(),
+    };
+
+    scenario.options = match self.options {
+      Some(ref options) => specs::Options {
+        timeout: options.timeout.unwrap_or(15),
+        cookies: options.cookies.unwrap_or(false),
+        virtual_users: options.virtual_users.unwrap_or(1),
+        connections: options.connections.as_ref().map_or_else(specs::Connections::default, |connections| specs::Connections {
+          reuse: connections.reuse.unwrap_or(true),
+          pool_size: connections.pool_size,
+          max_requests: connections.max_requests,
+          idle_timeout: connections.idle_timeout,
+        }),
+      },
+      None => specs::Options::default(),
+    };
+
+    // Each virtual user keeps its own cookies
+    if scenario.options.cookies {
+      scenario.users = (0..scenario.options.virtual_users).map(|_| specs::VirtualUser::default()).collect();
+    }
+
+    scenario.schedulers = self
+      .schedulers
+      .iter()
+      .map(|scheduler| match scheduler.kind {
+        ConfigSchedulerKind::Constant {
+          ref args,
+          ref upstreams,
+          ref weights,
+          selection,
+        } => Scheduler::Constant(strategies::Constant {
+          selector: self.selector(upstreams, weights, selection),
+          duration: args.duration,
+          count: args.count,
+          interval: args.interval,
+        }),
+
+        ConfigSchedulerKind::SteppedConstant {
+          ref steps,
+          ref upstreams,
+          ref weights,
+          selection,
+        } => Scheduler::SteppedConstant(strategies::SteppedConstant {
+          steps: steps
+            .iter()
+            .map(|step| strategies::Constant {
+              selector: self.selector(if step.upstreams.is_empty() { upstreams } else { &step.upstreams }, weights, selection),
+              duration: step.duration,
+              count: step.count,
+              interval: step.interval,
+            })
+            .collect(),
+        }),
+
+        ConfigSchedulerKind::DoubleEvery {
+          ref args,
+          ref upstreams,
+          ref weights,
+          selection,
+        } => Scheduler::DoubleEvery(strategies::DoubleEvery {
+          selector: self.selector(upstreams, weights, selection),
+          duration: args.duration,
+          period: args.period,
+          count: args.count,
+          interval: args.interval,
+        }),
+
+        ConfigSchedulerKind::RampUp {
+          ref args,
+          ref upstreams,
+          ref weights,
+          selection,
+        } => Scheduler::RampUp(strategies::RampUp {
+          selector: self.selector(upstreams, weights, selection),
+          duration: args.duration,
+          interval: args.interval,
+          from: args.from,
+          to: args.to,
+        }),
+
+        ConfigSchedulerKind::Pause { ref args } => Scheduler::Pause(strategies::Pause { duration: args.duration }),
+      })
+      .collect();
+
+    let mut auths = vec![];
+    let mut errors = vec![];
+    let timeout = scenario.options.timeout;
+    let options_tls = self.options.as_ref().and_then(|options| options.tls.as_ref());
+
+    scenario.upstreams = self
+      .upstreams
+      .iter()
+      .map(|original| {
+        let upstream = self.resolve_upstream(original).unwrap_or_default();
+        let tls = ConfigTls::merge(upstream.tls.clone(), options_tls);
+        let url = upstream.full_url().unwrap_or_default();
+
+        // Certificates and proto files are only read here. Problems with the
+        // scenario's TLS settings are reported once, against them.
+        let mut report = |attribute: &str, message: String| {
+          let error = match (attribute, &upstream.tls, &self.options) {
+            ("tls", None, Some(options)) => ConfigError::new(&options.origin("tls"), "tls", format!("options.tls: {}", message)),
+            _ => ConfigError::new(&original.origin, attribute, message),
+          };
+
+          if !errors.iter().any(|existing: &ConfigError| existing.to_string() == error.to_string()) {
+            errors.push(error);
+          }
+        };
+
+        specs::Upstream {
+          name: upstream.name.clone(),
+          kind: match (upstream.kind, &upstream.grpc, &upstream.socket) {
+            (Some(ConfigUpstreamKind::Websocket), _, _) => {
+              // Only `wss://` URLs use a connector, whose native TLS library
+              // can reject settings the HTTP client accepts
+              let connector = match tls.as_ref().map(ConfigTls::settings) {
+                Some(ref settings) if url.starts_with("wss://") => tls::websocket(settings).map_err(|message| report("tls", message)).ok(),
+                _ => None,
+              };
+
+              specs::Kind::Websocket(upstream.websocket.clone().unwrap_or_default().create(timeout, connector, tls.as_ref().and_then(|tls| tls.sni.clone())))
+            }
+
+            (Some(ConfigUpstreamKind::Grpc), Some(grpc), _) => {
+              let config = match tls.as_ref().map(ConfigTls::settings) {
+                Some(ref settings) if url.starts_with("https://") => tls::grpc(settings).map_err(|message| report("tls", message)).ok(),
+                _ => None,
+              };
+
+              let descriptor = match grpc.proto {
+                Some(ref proto) => grpc.load(proto).map_err(|message| report("grpc.proto", message)).ok(),
+                None => None,
+              };
+
+              match grpc.create(config, descriptor) {
+                Ok(grpc) => specs::Kind::Grpc(Box::new(grpc)),
+                Err(message) => {
+                  report("grpc.method", message);
+                  specs::Kind::Http
+                }
+              }
+            }
+
+            (Some(ConfigUpstreamKind::Tcp), _, Some(socket)) => specs::Kind::Socket(socket.create(specs::Transport::Tcp)),
+            (Some(ConfigUpstreamKind::Udp), _, Some(socket)) => specs::Kind::Socket(socket.create(specs::Transport::Udp)),
+            _ => specs::Kind::Http,
+          },
+          method: match upstream.method.as_ref().map(String::as_ref) {
+            Some("GET") => Get,
+            Some("POST") => Post,
+            _ => Unknown,
+          },
+          url: compile(&upstream.full_url().unwrap_or_default()),
+          weight: upstream.weight.unwrap_or(1.0),
+          headers: upstream.headers.iter().map(|(key, value)| (key.to_owned(), compile(value))).collect(),
+          basic: upstream.basic.as_ref().map(|basic| specs::UpstreamBasicAuth {
+            username: basic.username.to_owned(),
+            password: basic.password.to_owned(),
+          }),
+          auth: upstream.auth.as_ref().map(|auth| self.auth(auth, &mut auths)),
+          signing: upstream.signing.as_ref().map(ConfigSigning::create),
+          tls: tls.and_then(|tls| tls.create().map_err(|message| report("tls", message)).ok()),
+          http: specs::Http {
+            protocol: upstream.protocol.map(|protocol| match protocol {
+              ConfigProtocol::Http1 => specs::Protocol::Http1,
+              ConfigProtocol::H2c => specs::Protocol::H2c,
+              ConfigProtocol::H2 => specs::Protocol::H2,
+              ConfigProtocol::Http3 => specs::Protocol::Http3,
+            }),
+            redirects: match upstream.follow_redirects {
+              Some(false) => 0,
+              _ => upstream.max_redirects.unwrap_or(specs::REDIRECTS),
+            },
+            decompress: match upstream.decompress {
+              None | Some(ConfigDecompress::All(false)) => vec![],
+              Some(ConfigDecompress::All(true)) => vec![specs::Compression::Gzip, specs::Compression::Brotli, specs::Compression::Deflate],
+              Some(ConfigDecompress::Encodings(ref encodings)) => encodings
+                .iter()
+                .map(|encoding| match encoding {
+                  ConfigCompression::Gzip => specs::Compression::Gzip,
+                  ConfigCompression::Brotli => specs::Compression::Brotli,
+                  ConfigCompression::Deflate => specs::Compression::Deflate,
+                })
+                .collect(),
+            },
+          },
+          network: self.network(&upstream),
+          read_body: match upstream.read_body {
+            None | Some(ConfigReadBody::Mode(ConfigReadBodyMode::Discard)) => specs::ReadBody::Discard,
+            Some(ConfigReadBody::Mode(ConfigReadBodyMode::Full)) => specs::ReadBody::Full,
+            Some(ConfigReadBody::Bytes(limit)) => specs::ReadBody::Limit(limit),
+          },
+          pools: Mutex::default(),
+          body: upstream.create_body(),
+        }
+      })
+      .collect();
+
+    if !errors.is_empty() {
+      return Err(ConfigErrors(errors));
+    }
+
+    scenario.distribution = Arc::new(specs::Distribution::new(scenario.upstreams.iter().map(|upstream| upstream.name.clone()).collect()));
+
+    scenario.datasources = match self.datasources {
+      Some(ref datasources) => datasources
+        .iter()
+        .map(|(name, datasource)| {
+          let plugin = datasource.plugin();
+
+          let mode = match datasource.mode {
+            ConfigDatasourceMode::Random => datasource::Mode::Random,
+            ConfigDatasourceMode::Sequential => datasource::Mode::Sequential,
+            ConfigDatasourceMode::Once => datasource::Mode::Once,
+            ConfigDatasourceMode::Shuffle => datasource::Mode::Shuffle,
+          };
+
+          let exhausted = match datasource.on_exhausted {
+            Some(ConfigExhausted::Stop) => datasource::Exhausted::Stop,
+            _ => datasource::Exhausted::Fail,
+          };
+
+          (name.to_owned(), datasource::Pool::new(plugin, mode, exhausted))
+        })
+        .collect(),
+
+      None => scenario.datasources,
+    };
+
+    Ok(scenario)
+  }
+}
+
+// Templates are checked during the validation, before the scenario is created.
+fn compile(source: &str) -> Template {
+  Template::parse(source).expect("invalid template")
+}
+
+// Parsers append the position to their messages, which is already reported.
+fn strip_position(message: &str) -> String {
+  Regex::new(r" at line \d+ column \d+$").unwrap().replace(message, "").into_owned()
+}
+
+// Report parsing errors as `file:line:column: message`.
+fn parse_error(source: &Source, err: &serde_yaml::Error) -> ConfigError {
+  let message = strip_position(&err.to_string());
+
+  let location = err.location().map(|location| {
+    let field = Regex::new(r"unknown field `([^`]+)`").unwrap().captures(&message).map(|capture| capture[1].to_owned());
+
+    field
+      .and_then(|field| source.find_field(location.line(), location.column(), &field))
+      .unwrap_or_else(|| source.at(location.line(), location.column()))
+  });
+
+  ConfigError { location, message }
+}
+
+impl ConfigDatasource {
+  fn delimiter(&self) -> u8 {
+    self.delimiter.map(|delimiter| delimiter as u8).unwrap_or(b',')
+  }
+
+  fn plugin(&self) -> Box<dyn DataSource> {
+    match self {
+      ConfigDatasource { ref kind, source: Some(source), .. } if kind == "file" => Box::new(datasource::File::new(source)),
+      ConfigDatasource {
+        ref kind,
+        source: Some(source),
+        whole_files: true,
+        ..
+      } if kind == "directory" => Box::new(datasource::Directory::files(source, self.glob.as_deref(), self.binary)),
+      ConfigDatasource { ref kind, source: Some(source), .. } if kind == "directory" => Box::new(datasource::Directory::lines(source, self.glob.as_deref())),
+      ConfigDatasource { ref kind, source: Some(source), .. } if kind == "csv" => Box::new(datasource::Csv::new(source, self.delimiter())),
+      ConfigDatasource { ref kind, data: Some(data), .. } if kind == "array" => Box::new(datasource::Array::new(data)),
+      _ => Box::new(datasource::Array::new(&[])),
+    }
+  }
+
+  // Names of the columns of a CSV datasource, read from its first line.
+  fn columns(&self) -> Vec<String> {
+    match (self.kind.as_ref(), &self.source) {
+      ("csv", Some(source)) => csv::ReaderBuilder::new()
+        .delimiter(self.delimiter())
+        .from_path(source)
+        .and_then(|mut reader| reader.headers().map(|headers| headers.iter().map(str::to_owned).collect()))
+        .unwrap_or_default(),
+
+      _ => vec![],
+    }
+  }
+}
+
+impl ConfigUpstream {
+  // Fill every unset field from `parent`, headers are merged with the child's
+  // values taking precedence. The parent's own `extends` is carried over so the
+  // chain can be followed further.
+  fn inherit(mut self, parent: &ConfigUpstream) -> ConfigUpstream {
+    for (key, value) in &parent.headers {
+      self.headers.entry(key.to_owned()).or_insert_with(|| value.to_owned());
+    }
+
+    for (host, addr) in &parent.resolve {
+      self.resolve.entry(host.to_owned()).or_insert_with(|| addr.to_owned());
+    }
+
+    // The body is inherited as a whole, whatever its kind
+    if !self.has_body() {
+      self.body = parent.body.clone();
+      self.body_file = parent.body_file.clone();
+      self.form = parent.form.clone();
+      self.multipart = parent.multipart.clone();
+      self.json = parent.json.clone();
+    }
+
+    ConfigUpstream {
+      origin: self.origin,
+      name: self.name,
+      kind: self.kind.or(parent.kind),
+      extends: self.extends.or_else(|| parent.extends.clone()),
+      base_url: self.base_url.or_else(|| parent.base_url.clone()),
+      method: self.method.or_else(|| parent.method.clone()),
+      weight: self.weight.or(parent.weight),
+      url: self.url.or_else(|| parent.url.clone()),
+      headers: self.headers,
+      basic: self.basic.or_else(|| parent.basic.clone()),
+      auth: self.auth.or_else(|| parent.auth.clone()),
+      signing: self.signing.or_else(|| parent.signing.clone()),
+      tls: ConfigTls::merge(self.tls, parent.tls.as_ref()),
+      protocol: self.protocol.or(parent.protocol),
+      resolve: self.resolve,
+      proxy: self.proxy.or_else(|| parent.proxy.clone()),
+      bind_address: self.bind_address.or_else(|| parent.bind_address.clone()),
+      follow_redirects: self.follow_redirects.or(parent.follow_redirects),
+      max_redirects: self.max_redirects.or(parent.max_redirects),
+      decompress: self.decompress.or_else(|| parent.decompress.clone()),
+      read_body: self.read_body.or(parent.read_body),
+      websocket: self.websocket.or_else(|| parent.websocket.clone()),
+      grpc: self.grpc.or_else(|| parent.grpc.clone()),
+      socket: self.socket.or_else(|| parent.socket.clone()),
+      body: self.body,
+      body_file: self.body_file,
+      form: self.form,
+      multipart: self.multipart,
+      json: self.json,
+    }
+  }
+
+  // Attributes defining the body of the requests, only one of them can be set.
+  fn bodies(&self) -> Vec<&'static str> {
+    let mut bodies = vec![];
+
+    if self.body.is_some() {
+      bodies.push("body");
+    }
+    if self.body_file.is_some() {
+      bodies.push("body_file");
+    }
+    if self.form.is_some() {
+      bodies.push("form");
+    }
+    if self.multipart.is_some() {
+      bodies.push("multipart");
+    }
+    if self.json.is_some() {
+      bodies.push("json");
+    }
+
+    bodies
+  }
+
+  fn has_body(&self) -> bool {
+    !self.bodies().is_empty()
+  }
+
+  fn create_body(&self) -> Option<specs::Body> {
+    if let Some(ref body) = self.body {
+      return Some(specs::Body::Text(compile(body)));
+    }
+
+    if let Some(ref file) = self.body_file {
+      let content = fs::read(file.path()).unwrap_or_default();
+
+      return match file.templated() {
+        true => Some(specs::Body::Text(compile(&String::from_utf8_lossy(&content)))),
+        false => Some(specs::Body::Raw(content)),
+      };
+    }
+
+    if let Some(ref form) = self.form {
+      return Some(specs::Body::Form(form.iter().map(|(key, value)| (key.to_owned(), compile(value))).collect()));
+    }
+
+    if let Some(ref multipart) = self.multipart {
+      let parts = multipart
+        .iter()
+        .map(|(name, part)| {
+          let part = match part {
+            ConfigPart::Text(value) => specs::Part::Text(compile(value)),
+            ConfigPart::File(file) => specs::Part::File(specs::FilePart {
+              path: file.file.to_owned(),
+              filename: file.filename.to_owned(),
+              content_type: file.content_type.to_owned(),
+            }),
+          };
+
+          (name.to_owned(), part)
+        })
+        .collect();
+
+      return Some(specs::Body::Multipart(parts));
+    }
+
+    if let Some(ref json) = self.json {
+      return Some(specs::Body::Json(JsonTemplate::parse(json).expect("invalid template")));
+    }
+
+    None
+  }
+
+  // Relative URLs are appended to the upstream's base URL, if any.
+  fn full_url(&self) -> Option<String> {
+    match (&self.base_url, &self.url) {
+      (Some(base), Some(url)) if !url.contains("://") => Some(format!("{}/{}", base.trim_end_matches('/'), url.trim_start_matches('/'))),
+      (Some(base), None) => Some(base.to_owned()),
+      (_, url) => url.to_owned(),
+    }
+  }
+}
+
+#[derive(Debug)]
+pub struct Options {
+  pub verbose: bool,
+  pub log: bool,
+}
+
+impl Options {
+  fn new() -> Options {
+    Options { verbose: false, log: true }
+  }
+}
+
+pub fn parse_options(app: &clap::ArgMatches) -> Options {
+  app.args.iter().fold(Options::new(), |mut options, arg| match arg {
+    (&"verbose", _) => {
+      options.verbose = true;
+      options
+    }
+
+    (&"nolog", _) => {
+      options.log = false;
+      options
+    }
+
+    _ => options,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Errors of a scenario which is valid, but whose files cannot be read.
+  fn errors(content: &str) -> Vec<String> {
+    let source = Arc::new(Source::parse(Path::new("scenario.yml"), content));
+    let mut config = Config::parse(&source, content).unwrap();
+
+    config.set_origins(&source);
+
+    match config.create_scenario() {
+      Ok(_) => vec![],
+      Err(errors) => errors.0.into_iter().map(|error| error.message).collect(),
+    }
+  }
+
+  #[test]
+  fn reports_scenario_certificates_once() {
+    let errors = errors(
+      "options:\n  tls:\n    ca: /nonexistent/ca.pem\nschedulers: []\nupstreams:\n  - {name: a, method: GET, url: 'https://localhost/'}\n  - {name: b, method: GET, url: 'https://localhost/'}\n",
+    );
+
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert!(errors[0].starts_with("options.tls: could not read '/nonexistent/ca.pem'"), "{:?}", errors);
+  }
+
+  #[test]
+  fn reports_upstream_certificates() {
+    let errors = errors("schedulers: []\nupstreams:\n  - {name: a, method: GET, url: 'https://localhost/', tls: {cert: /nonexistent/cert.pem}}\n");
+
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert!(errors[0].starts_with("could not read '/nonexistent/cert.pem'"), "{:?}", errors);
+  }
+
+  #[test]
+  fn reports_proto_files() {
+    let errors = errors("schedulers: []\nupstreams:\n  - name: a\n    kind: grpc\n    url: 'http://localhost:50051'\n    grpc: {method: pkg.Service/Method, proto: /nonexistent/service.proto}\n");
+
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert!(errors[0].starts_with("could not read '/nonexistent/service.proto'"), "{:?}", errors);
+  }
+
+  #[test]
+  fn reports_invalid_method_paths() {
+    let errors = errors("schedulers: []\nupstreams:\n  - name: a\n    kind: grpc\n    url: 'http://localhost:50051'\n    grpc: {method: 'pkg.Service/Get Users', reflection: true}\n");
+
+    assert_eq!(errors, vec!["invalid method 'pkg.Service/Get Users'"]);
+  }
+}
@@ -0,0 +1,926 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+
+use reqwest::Url;
+
+use super::location::{Location, Origin};
+use super::*;
+use crate::generator::{self, Generator};
+use crate::template::{Expression, Template, COOKIE};
+
+#[derive(Debug)]
+pub struct ConfigError {
+  pub location: Option<Location>,
+  pub message: String,
+}
+
+impl ConfigError {
+  pub fn new(origin: &Origin, attribute: &str, message: String) -> ConfigError {
+    ConfigError {
+      location: origin.locate(attribute),
+      message,
+    }
+  }
+}
+
+impl fmt::Display for ConfigError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self.location {
+      Some(ref location) => write!(f, "{}: {}", location, self.message),
+      None => write!(f, "{}", self.message),
+    }
+  }
+}
+
+impl Error for ConfigError {}
+
+#[derive(Debug)]
+pub struct ConfigErrors(pub Vec<ConfigError>);
+
+impl fmt::Display for ConfigErrors {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "invalid configuration")?;
+
+    for error in &self.0 {
+      write!(f, "\n  {}", error)?;
+    }
+
+    Ok(())
+  }
+}
+
+impl Error for ConfigErrors {}
+
+impl Config {
+  // Check the semantics of the merged configuration, returning every problem
+  // found instead of stopping at the first one.
+  pub fn validate(&self) -> Vec<ConfigError> {
+    let mut errors = vec![];
+
+    if let Some(ref options) = self.options {
+      for message in options.tls.as_ref().map(ConfigTls::validate).unwrap_or_default() {
+        errors.push(ConfigError::new(&options.origin("tls"), "tls", format!("options.tls: {}", message)));
+      }
+
+      for message in options.connections.as_ref().map(ConfigConnections::validate).unwrap_or_default() {
+        errors.push(ConfigError::new(&options.origin("connections"), "connections", format!("options.connections: {}", message)));
+      }
+
+      for (attribute, message) in validate_network(&options.resolve, options.proxy.as_ref(), options.bind_address.as_ref()) {
+        errors.push(ConfigError::new(&options.origin(&attribute), &attribute, format!("options.{}: {}", attribute, message)));
+      }
+
+      if options.virtual_users == Some(0) {
+        errors.push(ConfigError::new(
+          &options.origin("virtual_users"),
+          "virtual_users",
+          "'options.virtual_users' must be at least 1".to_owned(),
+        ));
+      }
+    }
+
+    for scheduler in &self.schedulers {
+      self.validate_scheduler(scheduler, &mut errors);
+    }
+
+    let mut names = HashSet::new();
+
+    for upstream in &self.upstreams {
+      if upstream.name.is_empty() {
+        errors.push(ConfigError::new(&upstream.origin, "", "upstream has no name".to_owned()));
+      } else if !names.insert(upstream.name.as_str()) {
+        errors.push(ConfigError::new(&upstream.origin, "name", format!("duplicate upstream '{}'", upstream.name)));
+      }
+
+      self.validate_upstream(upstream, &mut errors);
+    }
+
+    for template in self.templates.values().chain(self.defaults.iter()) {
+      self.validate_placeholders(template, &mut errors);
+    }
+
+    if let Some(ref datasources) = self.datasources {
+      for datasource in datasources.values() {
+        validate_datasource(datasource, &mut errors);
+      }
+    }
+
+    errors
+  }
+
+  fn validate_scheduler(&self, scheduler: &ConfigScheduler, errors: &mut Vec<ConfigError>) {
+    let origin = &scheduler.origin;
+
+    match scheduler.kind {
+      ConfigSchedulerKind::Constant {
+        ref args, ref upstreams, ref weights, ..
+      } => {
+        validate_interval(origin, "args.interval", args.interval, errors);
+        validate_count(origin, "args.count", args.count, errors);
+        self.validate_filter(origin, "upstreams", upstreams, errors);
+        self.validate_weights(origin, weights, errors);
+        self.validate_selection(origin, "upstreams", upstreams, weights, errors);
+      }
+
+      ConfigSchedulerKind::SteppedConstant {
+        ref steps,
+        ref upstreams,
+        ref weights,
+        ..
+      } => {
+        if steps.is_empty() {
+          errors.push(ConfigError::new(origin, "steps", "scheduler 'SteppedConstant' requires at least one step".to_owned()));
+        }
+
+        for (idx, step) in steps.iter().enumerate() {
+          validate_interval(origin, &format!("steps.{}.interval", idx), step.interval, errors);
+          validate_count(origin, &format!("steps.{}.count", idx), step.count, errors);
+          self.validate_filter(origin, &format!("steps.{}.upstreams", idx), &step.upstreams, errors);
+
+          if !step.upstreams.is_empty() {
+            self.validate_selection(origin, &format!("steps.{}.upstreams", idx), &step.upstreams, weights, errors);
+          }
+        }
+
+        self.validate_filter(origin, "upstreams", upstreams, errors);
+        self.validate_weights(origin, weights, errors);
+
+        // Steps with their own filter do not use the scheduler's
+        if steps.iter().any(|step| step.upstreams.is_empty()) {
+          self.validate_selection(origin, "upstreams", upstreams, weights, errors);
+        }
+      }
+
+      ConfigSchedulerKind::DoubleEvery {
+        ref args, ref upstreams, ref weights, ..
+      } => {
+        validate_interval(origin, "args.interval", args.interval, errors);
+        validate_count(origin, "args.count", args.count, errors);
+        self.validate_filter(origin, "upstreams", upstreams, errors);
+        self.validate_weights(origin, weights, errors);
+        self.validate_selection(origin, "upstreams", upstreams, weights, errors);
+
+        // The count would never double with a longer period
+        if args.period == 0 || args.period > args.duration {
+          errors.push(ConfigError::new(origin, "args.period", format!("'period' must be between 1 and the duration ({}s)", args.duration)));
+        }
+      }
+
+      ConfigSchedulerKind::RampUp {
+        ref args, ref upstreams, ref weights, ..
+      } => {
+        validate_interval(origin, "args.interval", args.interval, errors);
+        validate_count(origin, "args.from", args.from, errors);
+        validate_count(origin, "args.to", args.to, errors);
+        self.validate_filter(origin, "upstreams", upstreams, errors);
+        self.validate_weights(origin, weights, errors);
+        self.validate_selection(origin, "upstreams", upstreams, weights, errors);
+
+        // Counts which are not finite are already reported
+        if args.from.is_finite() && args.to.is_finite() && args.to < args.from {
+          errors.push(ConfigError::new(
+            origin,
+            "args.to",
+            format!("'to' ({}) must be greater than or equal to 'from' ({})", args.to, args.from),
+          ));
+        }
+      }
+
+      ConfigSchedulerKind::Pause { .. } => {}
+    }
+  }
+
+  fn validate_weights(&self, origin: &Origin, weights: &HashMap<String, f64>, errors: &mut Vec<ConfigError>) {
+    for (name, weight) in weights {
+      if !self.upstreams.iter().any(|u| &u.name == name) {
+        errors.push(ConfigError::new(origin, &format!("weights.{}", name), format!("weight given to unknown upstream '{}'", name)));
+      }
+
+      validate_weight(origin, &format!("weights.{}", name), *weight, errors);
+    }
+  }
+
+  // A scheduler whose upstreams all weigh nothing would silently send no
+  // requests at all. Unknown upstreams are already reported by the filter.
+  fn validate_selection(&self, origin: &Origin, attribute: &str, upstreams: &[String], weights: &HashMap<String, f64>, errors: &mut Vec<ConfigError>) {
+    if upstreams.iter().any(|name| !self.upstreams.iter().any(|u| &u.name == name)) {
+      return;
+    }
+
+    let total: f64 = self
+      .upstreams
+      .iter()
+      .filter(|upstream| upstreams.is_empty() || upstreams.contains(&upstream.name))
+      .filter_map(|upstream| self.resolve_upstream(upstream).ok())
+      .map(|upstream| weights.get(&upstream.name).copied().or(upstream.weight).unwrap_or(1.0))
+      .filter(|weight| weight.is_finite() && *weight > 0.0)
+      .sum();
+
+    if total <= 0.0 {
+      errors.push(ConfigError::new(origin, attribute, "scheduler has no upstream with a weight greater than 0".to_owned()));
+    }
+  }
+
+  fn validate_filter(&self, origin: &Origin, attribute: &str, upstreams: &[String], errors: &mut Vec<ConfigError>) {
+    for name in upstreams {
+      if !self.upstreams.iter().any(|u| &u.name == name) {
+        errors.push(ConfigError::new(origin, attribute, format!("scheduler uses unknown upstream '{}'", name)));
+      }
+    }
+  }
+
+  fn validate_upstream(&self, upstream: &ConfigUpstream, errors: &mut Vec<ConfigError>) {
+    match self.resolve_upstream(upstream) {
+      Err(err) => errors.push(ConfigError::new(&upstream.origin, "extends", err)),
+
+      Ok(resolved) => {
+        // Only HTTP requests have a method
+        match (resolved.kind, resolved.method.as_ref().map(String::as_ref)) {
+          (Some(kind), _) if kind != ConfigUpstreamKind::Http => {}
+          (_, Some("GET")) | (_, Some("POST")) => {}
+          (_, Some(unknown)) => errors.push(ConfigError::new(&upstream.origin, "method", format!("unknown HTTP method '{}'", unknown))),
+          (_, None) => errors.push(ConfigError::new(&upstream.origin, "", format!("upstream '{}' has no HTTP method", upstream.name))),
+        }
+
+        if resolved.full_url().is_none() {
+          errors.push(ConfigError::new(&upstream.origin, "", format!("upstream '{}' has no URL", upstream.name)));
+        }
+
+        self.validate_auth(upstream, &resolved, errors);
+        validate_signing(upstream, &resolved, errors);
+        validate_protocol(upstream, &resolved, errors);
+        validate_response(upstream, &resolved, errors);
+        validate_websocket(upstream, &resolved, errors);
+        validate_grpc(upstream, &resolved, self.options.as_ref().and_then(|options| options.tls.as_ref()), errors);
+        validate_socket(upstream, &resolved, errors);
+
+        for (attribute, message) in validate_network(&resolved.resolve, resolved.proxy.as_ref(), resolved.bind_address.as_ref()) {
+          errors.push(ConfigError::new(&upstream.origin, &attribute, message));
+        }
+
+        // The scenario's settings are validated on their own, and only merged
+        // with the upstream's when they are valid
+        if let Some(ref tls) = resolved.tls {
+          let scenario = self.options.as_ref().and_then(|options| options.tls.as_ref());
+
+          let messages = match scenario.map(ConfigTls::validate) {
+            Some(messages) if !messages.is_empty() => tls.validate(),
+            _ => ConfigTls::merge(Some(tls.clone()), scenario).map(|tls| tls.validate()).unwrap_or_default(),
+          };
+
+          for message in messages {
+            errors.push(ConfigError::new(&upstream.origin, "tls", message));
+          }
+        }
+      }
+    }
+
+    if let Some(weight) = upstream.weight {
+      validate_weight(&upstream.origin, "weight", weight, errors);
+    }
+
+    validate_body(upstream, errors);
+    self.validate_placeholders(upstream, errors);
+  }
+
+  fn validate_auth(&self, upstream: &ConfigUpstream, resolved: &ConfigUpstream, errors: &mut Vec<ConfigError>) {
+    match resolved.auth {
+      Some(_) if resolved.basic.is_some() => {
+        errors.push(ConfigError::new(&upstream.origin, "auth", "'auth' cannot be used along with 'basic'".to_owned()));
+      }
+
+      Some(ConfigAuth::Login { upstream: ref login, .. }) if !self.upstreams.iter().any(|candidate| &candidate.name == login) => {
+        errors.push(ConfigError::new(&upstream.origin, "auth.upstream", format!("unknown login upstream '{}'", login)));
+      }
+
+      _ => {}
+    }
+  }
+
+  // Every `{label}` used in an upstream must refer to a declared datasource or
+  // to a generator with valid arguments.
+  fn validate_placeholders(&self, upstream: &ConfigUpstream, errors: &mut Vec<ConfigError>) {
+    let mut fields: Vec<(String, String)> = vec![];
+
+    if let Some(ref base_url) = upstream.base_url {
+      fields.push(("base_url".to_owned(), base_url.to_owned()));
+    }
+    if let Some(ref url) = upstream.url {
+      fields.push(("url".to_owned(), url.to_owned()));
+    }
+    if let Some(ref body) = upstream.body {
+      fields.push(("body".to_owned(), body.to_owned()));
+    }
+    if let Some(ref file) = upstream.body_file {
+      if let (true, Ok(content)) = (file.templated(), fs::read(file.path())) {
+        fields.push(("body_file".to_owned(), String::from_utf8_lossy(&content).into_owned()));
+      }
+    }
+    for (key, value) in &upstream.headers {
+      fields.push((format!("headers.{}", key), value.to_owned()));
+    }
+    for (key, value) in upstream.form.iter().flatten() {
+      fields.push((format!("form.{}", key), value.to_owned()));
+    }
+    for (key, part) in upstream.multipart.iter().flatten() {
+      if let ConfigPart::Text(value) = part {
+        fields.push((format!("multipart.{}", key), value.to_owned()));
+      }
+    }
+
+    if let Some(ref json) = upstream.json {
+      json_strings(json, "json", &mut fields);
+    }
+    if let Some(ref socket) = upstream.socket {
+      fields.push(("socket.send".to_owned(), socket.send.to_owned()));
+    }
+
+    for (attribute, value) in fields {
+      let template = match Template::parse(&value) {
+        Ok(template) => template,
+        Err(message) => {
+          errors.push(ConfigError::new(&upstream.origin, &attribute, message));
+          continue;
+        }
+      };
+
+      for expression in template.expressions() {
+        let label = &expression.label;
+
+        let datasource = match self.datasources.as_ref().and_then(|datasources| datasources.get(label)) {
+          Some(datasource) if expression.args.is_none() => datasource,
+          _ if label == COOKIE => {
+            self.validate_cookie(upstream, &attribute, expression, errors);
+            continue;
+          }
+          _ => {
+            if !generator::NAMES.contains(&label.as_str()) {
+              errors.push(ConfigError::new(
+                &upstream.origin,
+                &attribute,
+                format!("placeholder '{}' refers to an unknown datasource or generator", expression.source),
+              ));
+            } else if let Err(message) = Generator::parse(label, expression.column.as_deref(), expression.args.as_deref()) {
+              errors.push(ConfigError::new(&upstream.origin, &attribute, message));
+            }
+            continue;
+          }
+        };
+
+        let columns = datasource.columns();
+
+        match expression.column {
+          None if !columns.is_empty() => errors.push(ConfigError::new(
+            &upstream.origin,
+            &attribute,
+            format!("datasource '{}' has columns, use '{{{}.<column>}}'", label, label),
+          )),
+
+          Some(ref column) if !columns.contains(column) => {
+            errors.push(ConfigError::new(&upstream.origin, &attribute, format!("datasource '{}' has no column '{}'", label, column)));
+          }
+
+          _ => {}
+        }
+      }
+    }
+  }
+
+  // `{cookie.NAME}` reads a cookie of the virtual user sending the request,
+  // which is only known once the URL is rendered.
+  fn validate_cookie(&self, upstream: &ConfigUpstream, attribute: &str, expression: &Expression, errors: &mut Vec<ConfigError>) {
+    let message = if expression.column.is_none() || expression.args.is_some() {
+      format!("placeholder '{}' requires a cookie name, use '{{{}.<name>}}'", expression.source, COOKIE)
+    } else if !self.options.as_ref().and_then(|options| options.cookies).unwrap_or(false) {
+      format!("placeholder '{}' requires 'options.cookies' to be enabled", expression.source)
+    } else if attribute == "url" || attribute == "base_url" {
+      format!("placeholder '{}' cannot be used in '{}'", expression.source, attribute)
+    } else {
+      return;
+    };
+
+    errors.push(ConfigError::new(&upstream.origin, attribute, message));
+  }
+}
+
+impl ConfigOptions {
+  // Origin of the options of the last merged file setting the attribute, or
+  // else of the last file with options.
+  pub(super) fn origin(&self, attribute: &str) -> Origin {
+    self.origins.iter().rev().find(|origin| origin.defines(attribute)).or(self.origins.last()).cloned().unwrap_or_default()
+  }
+}
+
+impl ConfigConnections {
+  fn validate(&self) -> Vec<String> {
+    let mut messages = vec![];
+
+    if self.max_requests == Some(0) {
+      messages.push("'max_requests' must be at least 1".to_owned());
+    }
+
+    if self.reuse == Some(false) {
+      let settings = [
+        ("pool_size", self.pool_size.is_some()),
+        ("max_requests", self.max_requests.is_some()),
+        ("idle_timeout", self.idle_timeout.is_some()),
+      ];
+
+      for (setting, _) in settings.iter().filter(|(_, set)| *set) {
+        messages.push(format!("'{}' cannot be used without connection reuse", setting));
+      }
+    }
+
+    messages
+  }
+}
+
+impl ConfigTls {
+  fn validate(&self) -> Vec<String> {
+    let mut messages = vec![];
+
+    if self.cert.is_some() && self.pkcs12.is_some() {
+      messages.push("'cert' cannot be used along with 'pkcs12'".to_owned());
+    }
+    if self.key.is_some() && self.cert.is_none() {
+      messages.push("'key' requires 'cert'".to_owned());
+    }
+    if self.password.is_some() && self.pkcs12.is_none() {
+      messages.push("'password' requires 'pkcs12'".to_owned());
+    }
+    if self.pkcs12.is_some() && self.min_version == Some(ConfigTlsVersion::Tls13) {
+      messages.push("PKCS#12 client certificates cannot be used with a minimum version of 1.3".to_owned());
+    }
+    if self.cert.is_some() && matches!(self.min_version, Some(ConfigTlsVersion::Tls10) | Some(ConfigTlsVersion::Tls11)) {
+      messages.push("PEM client certificates require a minimum version of 1.2 or 1.3".to_owned());
+    }
+
+    messages
+  }
+}
+
+// Problems with the network settings of the scenario or of an upstream, along
+// with the attribute they concern.
+fn validate_network(resolve: &HashMap<String, String>, proxy: Option<&ConfigProxy>, bind_address: Option<&ConfigBindAddress>) -> Vec<(String, String)> {
+  let mut messages = vec![];
+
+  for (host, addr) in resolve {
+    if addr.parse::<IpAddr>().is_err() {
+      messages.push((format!("resolve.{}", host), format!("'{}' is not an IP address", addr)));
+    }
+  }
+
+  if let Some(proxy) = proxy {
+    let url = proxy.options().url;
+
+    match Url::parse(&url) {
+      Err(err) => messages.push(("proxy".to_owned(), format!("invalid proxy URL '{}': {}", url, err))),
+      Ok(url) if !["http", "https", "socks5", "socks5h"].contains(&url.scheme()) => {
+        messages.push(("proxy".to_owned(), format!("unsupported proxy scheme '{}', expected http, https, socks5 or socks5h", url.scheme())));
+      }
+      Ok(_) => {}
+    }
+  }
+
+  if let Some(bind_address) = bind_address {
+    let addrs = bind_address.addresses();
+
+    if addrs.is_empty() {
+      messages.push(("bind_address".to_owned(), "at least one address is required".to_owned()));
+    }
+
+    for addr in addrs.iter().filter(|addr| addr.parse::<IpAddr>().is_err()) {
+      messages.push(("bind_address".to_owned(), format!("'{}' is not an IP address", addr)));
+    }
+  }
+
+  messages
+}
+
+fn validate_websocket(upstream: &ConfigUpstream, resolved: &ConfigUpstream, errors: &mut Vec<ConfigError>) {
+  let origin = &upstream.origin;
+
+  if resolved.kind != Some(ConfigUpstreamKind::Websocket) {
+    if resolved.websocket.is_some() {
+      errors.push(ConfigError::new(origin, "websocket", "'websocket' can only be used with 'kind: websocket'".to_owned()));
+    }
+
+    return;
+  }
+
+  let url = resolved.full_url().unwrap_or_default();
+
+  if url.contains("://") && !url.starts_with("ws://") && !url.starts_with("wss://") {
+    errors.push(ConfigError::new(origin, "url", "websocket upstreams require a ws:// or wss:// URL".to_owned()));
+  }
+
+  // Sessions only send their handshake request, without a body, and read no
+  // HTTP response besides the handshake's
+  let mut unsupported = resolved.bodies();
+
+  let attributes = [
+    ("auth", resolved.auth.is_some()),
+    ("signing", resolved.signing.is_some()),
+    ("protocol", resolved.protocol.is_some()),
+    ("proxy", resolved.proxy.is_some()),
+    ("follow_redirects", resolved.follow_redirects.is_some()),
+    ("max_redirects", resolved.max_redirects.is_some()),
+    ("decompress", resolved.decompress.is_some()),
+    ("read_body", resolved.read_body.is_some()),
+  ];
+
+  unsupported.extend(attributes.iter().filter(|(_, set)| *set).map(|(attribute, _)| *attribute));
+
+  for attribute in unsupported {
+    errors.push(ConfigError::new(origin, attribute, format!("'{}' cannot be used with websocket upstreams", attribute)));
+  }
+
+  if let Some(ref websocket) = resolved.websocket {
+    if websocket.repeat == Some(0) {
+      errors.push(ConfigError::new(origin, "websocket.repeat", "'repeat' must be at least 1".to_owned()));
+    }
+
+    for (idx, message) in websocket.messages.iter().enumerate() {
+      if let Some(Err(err)) = message.expect.as_ref().map(|expect| Regex::new(expect)) {
+        errors.push(ConfigError::new(origin, &format!("websocket.messages.{}.expect", idx), format!("invalid pattern: {}", err)));
+      }
+    }
+  }
+}
+
+fn validate_grpc(upstream: &ConfigUpstream, resolved: &ConfigUpstream, tls: Option<&ConfigTls>, errors: &mut Vec<ConfigError>) {
+  let origin = &upstream.origin;
+
+  if resolved.kind != Some(ConfigUpstreamKind::Grpc) {
+    if resolved.grpc.is_some() {
+      errors.push(ConfigError::new(origin, "grpc", "'grpc' can only be used with 'kind: grpc'".to_owned()));
+    }
+
+    return;
+  }
+
+  let url = resolved.full_url().unwrap_or_default();
+
+  if url.contains("://") && !url.starts_with("http://") && !url.starts_with("https://") {
+    errors.push(ConfigError::new(origin, "url", "grpc upstreams require an http:// or https:// URL".to_owned()));
+  }
+
+  // The message of the calls is given as JSON, and the channels only support
+  // some of the network and TLS settings
+  let mut unsupported = resolved.bodies().into_iter().filter(|body| *body != "json").collect::<Vec<_>>();
+
+  let attributes = [
+    ("basic", resolved.basic.is_some()),
+    ("auth", resolved.auth.is_some()),
+    ("signing", resolved.signing.is_some()),
+    ("protocol", resolved.protocol.is_some()),
+    ("resolve", !resolved.resolve.is_empty()),
+    ("proxy", resolved.proxy.is_some()),
+    ("follow_redirects", resolved.follow_redirects.is_some()),
+    ("max_redirects", resolved.max_redirects.is_some()),
+    ("decompress", resolved.decompress.is_some()),
+    ("read_body", resolved.read_body.is_some()),
+  ];
+
+  unsupported.extend(attributes.iter().filter(|(_, set)| *set).map(|(attribute, _)| *attribute));
+
+  if url.starts_with("https://") {
+    let tls = ConfigTls::merge(resolved.tls.clone(), tls).unwrap_or_default();
+
+    let settings = [
+      ("tls.pkcs12", tls.pkcs12.is_some()),
+      ("tls.insecure_skip_verify", tls.insecure_skip_verify == Some(true)),
+      ("tls.min_version", tls.min_version.is_some()),
+    ];
+
+    unsupported.extend(settings.iter().filter(|(_, set)| *set).map(|(attribute, _)| *attribute));
+  }
+
+  for attribute in unsupported {
+    errors.push(ConfigError::new(origin, attribute, format!("'{}' cannot be used with grpc upstreams", attribute)));
+  }
+
+  let grpc = match resolved.grpc {
+    Some(ref grpc) => grpc,
+    None => {
+      errors.push(ConfigError::new(origin, "grpc", "grpc upstreams require a 'grpc' section with their method".to_owned()));
+      return;
+    }
+  };
+
+  if grpc.names().is_none() {
+    errors.push(ConfigError::new(origin, "grpc.method", format!("invalid method '{}', expected 'package.Service/Method'", grpc.method)));
+  }
+
+  match (&grpc.proto, grpc.reflection) {
+    (Some(_), true) => errors.push(ConfigError::new(origin, "grpc.reflection", "'proto' and 'reflection' cannot be used together".to_owned())),
+    (None, false) => errors.push(ConfigError::new(origin, "grpc", "grpc upstreams require a 'proto' file or 'reflection: true'".to_owned())),
+    _ => {}
+  }
+}
+
+fn validate_socket(upstream: &ConfigUpstream, resolved: &ConfigUpstream, errors: &mut Vec<ConfigError>) {
+  let origin = &upstream.origin;
+
+  let scheme = match resolved.kind {
+    Some(ConfigUpstreamKind::Tcp) => "tcp",
+    Some(ConfigUpstreamKind::Udp) => "udp",
+    _ => {
+      if resolved.socket.is_some() {
+        errors.push(ConfigError::new(origin, "socket", "'socket' can only be used with 'kind: tcp' or 'kind: udp'".to_owned()));
+      }
+
+      return;
+    }
+  };
+
+  let url = resolved.full_url().unwrap_or_default();
+
+  if url.contains("://") && !url.starts_with(&format!("{}://", scheme)) {
+    errors.push(ConfigError::new(origin, "url", format!("{} upstreams require a {}://host:port URL", scheme, scheme)));
+  }
+
+  // Only the addresses the payloads are sent to and from can be configured
+  let mut unsupported = resolved.bodies();
+
+  let attributes = [
+    ("headers", !resolved.headers.is_empty()),
+    ("basic", resolved.basic.is_some()),
+    ("auth", resolved.auth.is_some()),
+    ("signing", resolved.signing.is_some()),
+    ("tls", resolved.tls.is_some()),
+    ("protocol", resolved.protocol.is_some()),
+    ("proxy", resolved.proxy.is_some()),
+    ("follow_redirects", resolved.follow_redirects.is_some()),
+    ("max_redirects", resolved.max_redirects.is_some()),
+    ("decompress", resolved.decompress.is_some()),
+    ("read_body", resolved.read_body.is_some()),
+  ];
+
+  unsupported.extend(attributes.iter().filter(|(_, set)| *set).map(|(attribute, _)| *attribute));
+
+  for attribute in unsupported {
+    errors.push(ConfigError::new(origin, attribute, format!("'{}' cannot be used with {} upstreams", attribute, scheme)));
+  }
+
+  let socket = match resolved.socket {
+    Some(ref socket) => socket,
+    None => {
+      errors.push(ConfigError::new(origin, "socket", format!("{} upstreams require a 'socket' section with their payload", scheme)));
+      return;
+    }
+  };
+
+  if socket.hex {
+    validate_hex(origin, &socket.send, errors);
+  }
+
+  match (&socket.read_until, socket.read_bytes) {
+    (Some(_), Some(_)) => errors.push(ConfigError::new(origin, "socket.read_bytes", "'read_until' and 'read_bytes' cannot be used together".to_owned())),
+    (Some(delimiter), None) if delimiter.is_empty() => errors.push(ConfigError::new(origin, "socket.read_until", "'read_until' cannot be empty".to_owned())),
+    (None, Some(0)) => errors.push(ConfigError::new(origin, "socket.read_bytes", "'read_bytes' must be at least 1 byte".to_owned())),
+    (None, None) if socket.expect.is_some() => errors.push(ConfigError::new(origin, "socket.expect", "'expect' requires 'read_until' or 'read_bytes'".to_owned())),
+    _ => {}
+  }
+
+  if let Some(Err(err)) = socket.expect.as_ref().map(|expect| regex::bytes::Regex::new(expect)) {
+    errors.push(ConfigError::new(origin, "socket.expect", format!("invalid pattern: {}", err)));
+  }
+}
+
+// Hexadecimal payloads are decoded once rendered, so the text around their
+// placeholders must be made of whole bytes. Values are only checked when
+// sent, datasources being too large to be read at startup.
+fn validate_hex(origin: &Origin, send: &str, errors: &mut Vec<ConfigError>) {
+  let template = match Template::parse(send) {
+    Ok(template) => template,
+    Err(_) => return,
+  };
+
+  let invalid = template.texts().map(|text| specs::Socket::decode(text.as_bytes())).find(Result::is_err);
+
+  if let Some(Err(err)) = invalid {
+    errors.push(ConfigError::new(origin, "socket.send", format!("invalid hexadecimal payload: {}", err)));
+  }
+}
+
+fn validate_response(upstream: &ConfigUpstream, resolved: &ConfigUpstream, errors: &mut Vec<ConfigError>) {
+  if let (Some(false), Some(_)) = (resolved.follow_redirects, resolved.max_redirects) {
+    errors.push(ConfigError::new(
+      &upstream.origin,
+      "max_redirects",
+      "'max_redirects' cannot be used when redirects are not followed".to_owned(),
+    ));
+  }
+
+  if let Some(ConfigReadBody::Bytes(0)) = resolved.read_body {
+    errors.push(ConfigError::new(
+      &upstream.origin,
+      "read_body",
+      "'read_body' must be at least 1 byte, use 'discard' to skip the body".to_owned(),
+    ));
+  }
+}
+
+// Cleartext HTTP/2 requires a plain HTTP URL, and the other versions of HTTP/2
+// and HTTP/3 are negotiated over TLS.
+fn validate_protocol(upstream: &ConfigUpstream, resolved: &ConfigUpstream, errors: &mut Vec<ConfigError>) {
+  let url = resolved.full_url().unwrap_or_default();
+
+  let message = match resolved.protocol {
+    Some(ConfigProtocol::H2c) if url.starts_with("https://") => "protocol 'h2c' cannot be used with an HTTPS URL",
+    Some(ConfigProtocol::H2) if url.starts_with("http://") => "protocol 'h2' requires an HTTPS URL, use 'h2c' for cleartext HTTP/2",
+    Some(ConfigProtocol::Http3) if url.starts_with("http://") => "protocol 'http3' requires an HTTPS URL",
+    Some(ConfigProtocol::Http3) if !cfg!(feature = "http3") => "protocol 'http3' requires candia to be built with the http3 feature",
+    _ => return,
+  };
+
+  errors.push(ConfigError::new(&upstream.origin, "protocol", message.to_owned()));
+}
+
+fn validate_signing(upstream: &ConfigUpstream, resolved: &ConfigUpstream, errors: &mut Vec<ConfigError>) {
+  match resolved.signing {
+    Some(ConfigSigning::Hmac { components: Some(ref components), .. }) => {
+      for (idx, component) in components.iter().enumerate() {
+        if specs::Component::parse(component).is_none() {
+          errors.push(ConfigError::new(
+            &upstream.origin,
+            &format!("signing.components.{}", idx),
+            format!("unknown signing component '{}', expected method, path, date, body or header:<name>", component),
+          ));
+        }
+      }
+    }
+
+    // SigV4 sets the Authorization header itself
+    Some(ConfigSigning::AwsSigv4 { .. }) => {
+      let others = [("basic", resolved.basic.is_some()), ("auth", resolved.auth.is_some())];
+
+      for (other, _) in others.iter().filter(|(_, set)| *set) {
+        errors.push(ConfigError::new(&upstream.origin, "signing", format!("'aws_sigv4' signing cannot be used along with '{}'", other)));
+      }
+    }
+
+    _ => {}
+  }
+}
+
+// Strings of a JSON document, with their path.
+fn json_strings(value: &serde_json::Value, path: &str, fields: &mut Vec<(String, String)>) {
+  match value {
+    serde_json::Value::String(value) => fields.push((path.to_owned(), value.to_owned())),
+    serde_json::Value::Array(values) => {
+      for (idx, value) in values.iter().enumerate() {
+        json_strings(value, &format!("{}.{}", path, idx), fields);
+      }
+    }
+    serde_json::Value::Object(values) => {
+      for (key, value) in values {
+        json_strings(value, &format!("{}.{}", path, key), fields);
+      }
+    }
+    _ => {}
+  }
+}
+
+fn validate_body(upstream: &ConfigUpstream, errors: &mut Vec<ConfigError>) {
+  let bodies = upstream.bodies();
+
+  if bodies.len() > 1 {
+    errors.push(ConfigError::new(&upstream.origin, bodies[1], format!("'{}' cannot be used along with '{}'", bodies[1], bodies[0])));
+  }
+
+  if let Some(ref file) = upstream.body_file {
+    if !Path::new(file.path()).is_file() {
+      errors.push(ConfigError::new(&upstream.origin, "body_file", format!("file '{}' does not exist", file.path())));
+    }
+  }
+
+  for (key, part) in upstream.multipart.iter().flatten() {
+    if let ConfigPart::File(file) = part {
+      if !Path::new(&file.file).is_file() {
+        errors.push(ConfigError::new(&upstream.origin, &format!("multipart.{}.file", key), format!("file '{}' does not exist", file.file)));
+      }
+    }
+  }
+}
+
+fn validate_weight(origin: &Origin, attribute: &str, weight: f64, errors: &mut Vec<ConfigError>) {
+  if !weight.is_finite() || weight < 0.0 {
+    errors.push(ConfigError::new(origin, attribute, format!("invalid weight '{}'", weight)));
+  }
+}
+
+fn validate_interval(origin: &Origin, attribute: &str, interval: u64, errors: &mut Vec<ConfigError>) {
+  if interval == 0 {
+    errors.push(ConfigError::new(origin, attribute, "'interval' must be greater than 0".to_owned()));
+  }
+}
+
+fn validate_count(origin: &Origin, attribute: &str, count: f64, errors: &mut Vec<ConfigError>) {
+  if !count.is_finite() || count < 0.0 {
+    errors.push(ConfigError::new(origin, attribute, format!("invalid request count '{}'", count)));
+  }
+}
+
+fn validate_datasource(datasource: &ConfigDatasource, errors: &mut Vec<ConfigError>) {
+  if datasource.delimiter.map(|delimiter| !delimiter.is_ascii()).unwrap_or(false) {
+    errors.push(ConfigError::new(&datasource.origin, "delimiter", "'delimiter' must be an ASCII character".to_owned()));
+  }
+
+  if datasource.on_exhausted.is_some() && datasource.mode != ConfigDatasourceMode::Once {
+    errors.push(ConfigError::new(
+      &datasource.origin,
+      "on_exhausted",
+      "'on_exhausted' only applies to datasources in the 'once' mode".to_owned(),
+    ));
+  }
+
+  if datasource.kind != "directory" {
+    for (attribute, set) in &[("glob", datasource.glob.is_some()), ("whole_files", datasource.whole_files), ("binary", datasource.binary)] {
+      if *set {
+        errors.push(ConfigError::new(
+          &datasource.origin,
+          attribute,
+          format!("'{}' only applies to datasources of kind 'directory'", attribute),
+        ));
+      }
+    }
+  } else if datasource.binary && !datasource.whole_files {
+    errors.push(ConfigError::new(&datasource.origin, "binary", "'binary' requires 'whole_files'".to_owned()));
+  }
+
+  if let Some(ref glob) = datasource.glob {
+    if let Err(err) = glob::Pattern::new(glob) {
+      errors.push(ConfigError::new(&datasource.origin, "glob", format!("invalid pattern '{}': {}", glob, err.msg)));
+    }
+  }
+
+  match (datasource.kind.as_ref(), &datasource.source, &datasource.data) {
+    ("file", Some(source), _) | ("csv", Some(source), _) if !Path::new(source).is_file() => errors.push(ConfigError::new(&datasource.origin, "source", format!("file '{}' does not exist", source))),
+    ("directory", Some(source), _) if !Path::new(source).is_dir() => errors.push(ConfigError::new(&datasource.origin, "source", format!("directory '{}' does not exist", source))),
+    ("file", None, _) | ("directory", None, _) | ("csv", None, _) => errors.push(ConfigError::new(&datasource.origin, "", format!("datasource of kind '{}' requires a 'source'", datasource.kind))),
+    ("array", _, None) => errors.push(ConfigError::new(&datasource.origin, "", "datasource of kind 'array' requires 'data'".to_owned())),
+    ("file", ..) | ("directory", ..) | ("csv", ..) | ("array", ..) => {}
+    (unknown, ..) => errors.push(ConfigError::new(&datasource.origin, "kind", format!("unknown datasource kind '{}'", unknown))),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::path::Path;
+  use std::sync::Arc;
+
+  use crate::config::location::Source;
+
+  // Messages of the errors found in a YAML scenario.
+  fn validate(content: &str) -> Vec<String> {
+    let source = Arc::new(Source::parse(Path::new("scenario.yml"), content));
+    let mut config = Config::parse(&source, content).unwrap();
+
+    config.set_origins(&source);
+    config.validate().into_iter().map(|error| error.message).collect()
+  }
+
+  const UPSTREAMS: &str = "
+upstreams:
+  - {name: read, method: GET, url: 'http://localhost/', weight: 0}
+  - {name: write, method: GET, url: 'http://localhost/'}
+";
+
+  #[test]
+  fn rejects_schedulers_without_weight() {
+    let errors = validate(&format!(
+      "schedulers:\n  - kind: Constant\n    args: {{duration: 1, count: 1, interval: 1}}\n    weights: {{write: 0}}\n{}",
+      UPSTREAMS
+    ));
+
+    assert_eq!(errors, vec!["scheduler has no upstream with a weight greater than 0"]);
+  }
+
+  #[test]
+  fn rejects_steps_without_weight() {
+    let errors = validate(&format!(
+      "schedulers:\n  - kind: SteppedConstant\n    steps:\n      - {{duration: 1, count: 1, interval: 1, upstreams: [read]}}\n      - {{duration: 1, count: 1, interval: 1}}\n{}",
+      UPSTREAMS
+    ));
+
+    assert_eq!(errors, vec!["scheduler has no upstream with a weight greater than 0"]);
+  }
+
+  #[test]
+  fn accepts_overridden_weights() {
+    let errors = validate(&format!(
+      "schedulers:\n  - kind: Constant\n    args: {{duration: 1, count: 1, interval: 1}}\n    upstreams: [read]\n    weights: {{read: 2}}\n{}",
+      UPSTREAMS
+    ));
+
+    assert!(errors.is_empty(), "{:?}", errors);
+  }
+}
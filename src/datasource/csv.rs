@@ -0,0 +1,148 @@
+use std::path::Path;
+
+use csv::{ByteRecord, Reader, ReaderBuilder};
+use memmap2::Mmap;
+
+use super::lines::{self, STRIDE};
+use super::Record;
+
+// Rows of a CSV file, whose first line holds the names of the columns. Rows
+// are indexed by their offset rather than by line, since quoted values can
+// span several lines.
+#[derive(Debug)]
+pub struct Csv {
+  delimiter: u8,
+  columns: Vec<String>,
+  mmap: Option<Mmap>,
+  checkpoints: Vec<usize>,
+  count: usize,
+}
+
+impl Csv {
+  pub fn new<T: AsRef<Path>>(path: T, delimiter: u8) -> Csv {
+    let mut csv = Csv {
+      delimiter,
+      columns: vec![],
+      mmap: lines::map(path).ok().flatten(),
+      checkpoints: vec![],
+      count: 0,
+    };
+
+    if let Some(ref mmap) = csv.mmap {
+      let mut reader = reader(mmap, delimiter, true);
+      let mut record = ByteRecord::new();
+
+      if let Ok(headers) = reader.byte_headers() {
+        csv.columns = headers.iter().map(|header| String::from_utf8_lossy(header).into_owned()).collect();
+      }
+
+      loop {
+        let position = reader.position().byte() as usize;
+
+        match reader.read_byte_record(&mut record) {
+          Ok(true) => {
+            if csv.count.is_multiple_of(STRIDE) {
+              csv.checkpoints.push(position);
+            }
+
+            csv.count += 1;
+          }
+
+          _ => break,
+        }
+      }
+    }
+
+    csv
+  }
+}
+
+fn reader(data: &[u8], delimiter: u8, has_headers: bool) -> Reader<&[u8]> {
+  ReaderBuilder::new().delimiter(delimiter).flexible(true).has_headers(has_headers).from_reader(data)
+}
+
+impl super::DataSource for Csv {
+  fn columns(&self) -> Vec<String> {
+    self.columns.clone()
+  }
+
+  fn len(&self) -> usize {
+    self.count
+  }
+
+  fn get(&self, idx: usize) -> Option<Record> {
+    let mmap = self.mmap.as_ref()?;
+
+    if idx >= self.count {
+      return None;
+    }
+
+    let mut reader = reader(&mmap[self.checkpoints[idx / STRIDE]..], self.delimiter, false);
+    let mut record = ByteRecord::new();
+
+    for _ in 0..=idx % STRIDE {
+      if !reader.read_byte_record(&mut record).ok()? {
+        return None;
+      }
+    }
+
+    Some(record.iter().map(<[u8]>::to_vec).collect())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::datasource::DataSource;
+
+  use std::env;
+  use std::fs;
+  use std::process;
+
+  fn csv(name: &str, content: &str) -> Csv {
+    let path = env::temp_dir().join(format!("candia-csv-{}-{}.csv", process::id(), name));
+
+    fs::write(&path, content).unwrap();
+
+    let csv = Csv::new(&path, b',');
+
+    fs::remove_file(&path).unwrap();
+    csv
+  }
+
+  fn row(values: &[&str]) -> Option<Record> {
+    Some(values.iter().map(|value| value.as_bytes().to_vec()).collect())
+  }
+
+  #[test]
+  fn reads_rows_around_checkpoints() {
+    let mut content = String::from("id,name\n");
+
+    for n in 0..STRIDE * 2 + 1 {
+      content.push_str(&format!("{},\"user\n{}\"\n", n, n));
+    }
+
+    let csv = csv("checkpoints", &content);
+
+    assert_eq!(csv.columns(), vec!["id", "name"]);
+    assert_eq!(csv.len(), STRIDE * 2 + 1);
+
+    for idx in [0, STRIDE - 1, STRIDE, STRIDE * 2] {
+      let name = format!("user\n{}", idx);
+
+      assert_eq!(csv.get(idx), row(&[&idx.to_string(), &name]));
+    }
+
+    assert_eq!(csv.get(STRIDE * 2 + 1), None);
+  }
+
+  #[test]
+  fn headers_alone_have_no_rows() {
+    let csv = csv("headers", "id,name\n");
+
+    assert_eq!(csv.columns(), vec!["id", "name"]);
+    assert_eq!(csv.len(), 0);
+    assert_eq!(csv.get(0), None);
+  }
+}
@@ -1,28 +1,176 @@
-use std::fs::{self, File};
-use std::io::{BufRead, BufReader};
-use std::path::Path;
+use glob::Pattern;
 
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::lines::Lines;
+use super::Record;
+
+// Files of a directory, in the order of their names, either split in lines or
+// whole.
+#[derive(Debug)]
 pub struct Directory {
-  files: Vec<File>,
+  paths: Vec<PathBuf>,
+  content: Content,
+}
+
+#[derive(Debug)]
+enum Content {
+  Lines {
+    files: Vec<Lines>,
+    // Index of the first line of each file
+    starts: Vec<usize>,
+    count: usize,
+  },
+  // Files are only read when used, text files without their final newline
+  Files {
+    binary: bool,
+  },
 }
 
 impl Directory {
-  pub fn new<T: AsRef<Path>>(path: T) -> Directory {
-    match fs::read_dir(path) {
-      Err(_) => Directory { files: vec![] },
-      Ok(files) => {
-        let files = files.filter_map(Result::ok).map(|file| File::open(file.path())).filter_map(Result::ok).collect();
+  pub fn lines<T: AsRef<Path>>(path: T, glob: Option<&str>) -> Directory {
+    let paths = list(path, glob);
+    let files: Vec<Lines> = paths.iter().filter_map(|path| Lines::open(path).ok()).collect();
+    let mut starts = vec![];
+    let mut count = 0;
 
-        Directory { files }
-      }
+    for file in &files {
+      starts.push(count);
+      count += file.len();
+    }
+
+    Directory {
+      paths,
+      content: Content::Lines { files, starts, count },
+    }
+  }
+
+  pub fn files<T: AsRef<Path>>(path: T, glob: Option<&str>, binary: bool) -> Directory {
+    Directory {
+      paths: list(path, glob),
+      content: Content::Files { binary },
     }
   }
 }
 
+// Sorted paths of the files of a directory whose names match the pattern.
+fn list<T: AsRef<Path>>(path: T, glob: Option<&str>) -> Vec<PathBuf> {
+  let pattern = glob.and_then(|glob| Pattern::new(glob).ok());
+
+  let mut paths: Vec<PathBuf> = match fs::read_dir(path) {
+    Err(_) => vec![],
+    Ok(entries) => entries
+      .filter_map(Result::ok)
+      .map(|entry| entry.path())
+      .filter(|path| path.is_file())
+      .filter(|path| match (&pattern, path.file_name()) {
+        (Some(pattern), Some(name)) => pattern.matches(&name.to_string_lossy()),
+        _ => true,
+      })
+      .collect(),
+  };
+
+  paths.sort();
+  paths
+}
+
 impl super::DataSource for Directory {
-  fn iter(self) -> super::Data {
-    self.files.iter().flat_map(|file| BufReader::new(file).lines().filter_map(Result::ok)).collect()
+  fn len(&self) -> usize {
+    match self.content {
+      Content::Lines { count, .. } => count,
+      Content::Files { .. } => self.paths.len(),
+    }
+  }
+
+  fn get(&self, idx: usize) -> Option<Record> {
+    match self.content {
+      Content::Lines { ref files, ref starts, count, .. } => {
+        if idx >= count {
+          return None;
+        }
+
+        let file = starts.partition_point(|start| *start <= idx) - 1;
+
+        files[file].get(idx - starts[file]).map(|line| vec![line.to_vec()])
+      }
+
+      Content::Files { binary } => {
+        let content = fs::read(self.paths.get(idx)?).ok()?;
+
+        if binary {
+          return Some(vec![content]);
+        }
+
+        let text = String::from_utf8_lossy(&content);
+
+        Some(vec![text.strip_suffix('\n').map(|text| text.strip_suffix('\r').unwrap_or(text)).unwrap_or(&text).as_bytes().to_vec()])
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::datasource::DataSource;
+
+  use std::env;
+  use std::process;
+
+  // Directory holding the given files, removed once read.
+  fn directory<F: FnOnce(&Path) -> Directory>(name: &str, files: &[(&str, &[u8])], open: F) -> Directory {
+    let path = env::temp_dir().join(format!("candia-directory-{}-{}", process::id(), name));
+
+    fs::create_dir_all(&path).unwrap();
+
+    for (file, content) in files {
+      fs::write(path.join(file), content).unwrap();
+    }
+
+    let directory = open(&path);
+
+    fs::remove_dir_all(&path).unwrap();
+    directory
+  }
+
+  #[test]
+  fn reads_the_lines_of_every_file_in_order() {
+    let directory = directory("lines", &[("b.txt", b"3\n"), ("a.txt", b"1\n2\n"), ("c.log", b"4\n")], |path| Directory::lines(path, Some("*.txt")));
+
+    assert_eq!(directory.len(), 3);
+    assert_eq!(
+      (0..4).map(|idx| directory.get(idx)).collect::<Vec<_>>(),
+      vec![Some(vec![b"1".to_vec()]), Some(vec![b"2".to_vec()]), Some(vec![b"3".to_vec()]), None]
+    );
+  }
+
+  #[test]
+  fn reads_whole_files() {
+    let files: &[(&str, &[u8])] = &[("1.json", b"{\"id\": 1}\r\n"), ("2.bin", &[0, 159, 146, 150, 10])];
+    let path = env::temp_dir().join(format!("candia-directory-{}-files", process::id()));
+
+    fs::create_dir_all(&path).unwrap();
+
+    for (file, content) in files {
+      fs::write(path.join(file), content).unwrap();
+    }
+
+    let text = Directory::files(&path, None, false);
+    let binary = Directory::files(&path, Some("*.bin"), true);
+
+    assert_eq!(text.len(), 2);
+    assert_eq!(text.get(0), Some(vec![b"{\"id\": 1}".to_vec()]));
+    assert_eq!(binary.len(), 1);
+    assert_eq!(binary.get(0), Some(vec![vec![0, 159, 146, 150, 10]]));
+
+    fs::remove_dir_all(&path).unwrap();
+  }
 
-    // Some(file) => BufReader::new(file).lines().filter_map(Result::ok).collect(),
+  #[test]
+  fn missing_directories_are_empty() {
+    assert!(Directory::lines("/nonexistent/candia", None).is_empty());
+    assert!(Directory::files("/nonexistent/candia", None, false).is_empty());
   }
 }
@@ -1,25 +1,28 @@
-use std::fs::File as OsFile;
-use std::io::{BufRead, BufReader};
 use std::path::Path;
 
+use super::lines::Lines;
+use super::Record;
+
+// Lines of a text file, read from the file when they are used.
+#[derive(Debug)]
 pub struct File {
-  file: Option<OsFile>,
+  lines: Lines,
 }
 
 impl File {
   pub fn new<T: AsRef<Path>>(path: T) -> File {
-    match OsFile::open(path) {
-      Err(_) => File { file: None },
-      Ok(file) => File { file: Some(file) },
+    File {
+      lines: Lines::open(path).unwrap_or_else(|_| Lines::empty()),
     }
   }
 }
 
 impl super::DataSource for File {
-  fn iter(self) -> super::Data {
-    match self.file {
-      None => vec![],
-      Some(file) => BufReader::new(file).lines().filter_map(Result::ok).collect(),
-    }
+  fn len(&self) -> usize {
+    self.lines.len()
+  }
+
+  fn get(&self, idx: usize) -> Option<Record> {
+    self.lines.get(idx).map(|line| vec![line.to_vec()])
   }
 }
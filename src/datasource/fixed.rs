@@ -1,5 +1,8 @@
+use super::Record;
+
+#[derive(Debug)]
 pub struct Array {
-  vec: super::Data,
+  vec: Vec<String>,
 }
 
 impl Array {
@@ -9,7 +12,11 @@ impl Array {
 }
 
 impl super::DataSource for Array {
-  fn iter(self) -> super::Data {
-    self.vec
+  fn len(&self) -> usize {
+    self.vec.len()
+  }
+
+  fn get(&self, idx: usize) -> Option<Record> {
+    self.vec.get(idx).map(|value| vec![value.clone().into_bytes()])
   }
 }
@@ -0,0 +1,159 @@
+use memchr::memchr;
+use memmap2::Mmap;
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+// Only the offset of one entry out of STRIDE is kept in memory, the others are
+// found by scanning forward from the closest one.
+pub const STRIDE: usize = 64;
+
+// Maps a whole file in memory, empty files cannot be mapped.
+pub fn map<T: AsRef<Path>>(path: T) -> io::Result<Option<Mmap>> {
+  let file = File::open(path)?;
+
+  if file.metadata()?.len() == 0 {
+    return Ok(None);
+  }
+
+  // The file is expected not to be modified while the scenario is running
+  unsafe { Mmap::map(&file).map(Some) }
+}
+
+// Lines of a memory-mapped file, indexed so any of them can be read without
+// loading the whole file.
+#[derive(Debug)]
+pub struct Lines {
+  mmap: Option<Mmap>,
+  checkpoints: Vec<usize>,
+  count: usize,
+}
+
+impl Lines {
+  pub fn open<T: AsRef<Path>>(path: T) -> io::Result<Lines> {
+    let mmap = map(path)?;
+    let mut checkpoints = vec![];
+    let mut count: usize = 0;
+
+    if let Some(ref mmap) = mmap {
+      let mut start = 0;
+
+      while start < mmap.len() {
+        if count.is_multiple_of(STRIDE) {
+          checkpoints.push(start);
+        }
+
+        count += 1;
+        start = next(mmap, start);
+      }
+    }
+
+    Ok(Lines { mmap, checkpoints, count })
+  }
+
+  pub fn empty() -> Lines {
+    Lines {
+      mmap: None,
+      checkpoints: vec![],
+      count: 0,
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    self.count
+  }
+
+  // Content of a line, without its line terminator.
+  pub fn get(&self, idx: usize) -> Option<&[u8]> {
+    let mmap = self.mmap.as_ref()?;
+
+    if idx >= self.count {
+      return None;
+    }
+
+    let mut start = self.checkpoints[idx / STRIDE];
+
+    for _ in 0..idx % STRIDE {
+      start = next(mmap, start);
+    }
+
+    let line = &mmap[start..next(mmap, start)];
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+
+    Some(line.strip_suffix(b"\r").unwrap_or(line))
+  }
+}
+
+// Offset of the line following the one starting at the given offset.
+fn next(data: &[u8], start: usize) -> usize {
+  match memchr(b'\n', &data[start..]) {
+    Some(end) => start + end + 1,
+    None => data.len(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::env;
+  use std::fs;
+  use std::process;
+
+  fn lines(name: &str, content: &str) -> Lines {
+    let path = env::temp_dir().join(format!("candia-lines-{}-{}.txt", process::id(), name));
+
+    fs::write(&path, content).unwrap();
+
+    let lines = Lines::open(&path).unwrap();
+
+    fs::remove_file(&path).unwrap();
+    lines
+  }
+
+  #[test]
+  fn reads_lines_around_checkpoints() {
+    let content: String = (0..STRIDE * 2 + 1).map(|n| format!("line {}\n", n)).collect();
+    let lines = lines("checkpoints", &content);
+
+    assert_eq!(lines.len(), STRIDE * 2 + 1);
+    assert_eq!(lines.checkpoints.len(), 3);
+
+    for idx in [0, STRIDE - 1, STRIDE, STRIDE + 1, STRIDE * 2 - 1, STRIDE * 2] {
+      assert_eq!(lines.get(idx), Some(format!("line {}", idx).as_bytes()));
+    }
+
+    assert_eq!(lines.get(STRIDE * 2 + 1), None);
+  }
+
+  #[test]
+  fn reads_a_full_stride() {
+    let content: String = (0..STRIDE).map(|n| format!("{}\n", n)).collect();
+    let lines = lines("stride", &content);
+
+    assert_eq!(lines.len(), STRIDE);
+    assert_eq!(lines.checkpoints.len(), 1);
+    assert_eq!(lines.get(STRIDE - 1), Some(format!("{}", STRIDE - 1).as_bytes()));
+    assert_eq!(lines.get(STRIDE), None);
+  }
+
+  #[test]
+  fn strips_line_terminators() {
+    let lines = lines("terminators", "a\r\n\nb");
+
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines.get(0), Some(&b"a"[..]));
+    assert_eq!(lines.get(1), Some(&b""[..]));
+    assert_eq!(lines.get(2), Some(&b"b"[..]));
+  }
+
+  #[test]
+  fn empty_files_have_no_lines() {
+    let lines = lines("empty", "");
+
+    assert_eq!(lines.len(), 0);
+    assert_eq!(lines.get(0), None);
+    assert_eq!(Lines::empty().get(0), None);
+  }
+}
@@ -1,13 +1,34 @@
+mod csv;
 mod directory;
 mod file;
 mod fixed;
+mod lines;
+mod pool;
 
+pub use self::csv::*;
 pub use self::directory::*;
 pub use self::file::*;
 pub use self::fixed::*;
+pub use self::pool::*;
 
-pub type Data = Vec<String>;
+use std::fmt;
 
-pub trait DataSource: Send {
-  fn iter(self) -> Data;
+// A record holds a single value, or one value per column for datasources with
+// named columns. Values are kept as bytes since they can be binary.
+pub type Record = Vec<Vec<u8>>;
+
+// Datasources are read when their records are used, so large files do not
+// have to fit in memory.
+pub trait DataSource: Send + Sync + fmt::Debug {
+  fn columns(&self) -> Vec<String> {
+    vec![]
+  }
+
+  fn len(&self) -> usize;
+
+  fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  fn get(&self, idx: usize) -> Option<Record>;
 }
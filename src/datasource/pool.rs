@@ -0,0 +1,210 @@
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::{DataSource, Record};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+  Random,
+  Sequential,
+  Once,
+  Shuffle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Exhausted {
+  Fail,
+  Stop,
+}
+
+impl fmt::Display for Mode {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Mode::Random => write!(f, "random"),
+      Mode::Sequential => write!(f, "sequential"),
+      Mode::Once => write!(f, "once"),
+      Mode::Shuffle => write!(f, "shuffle"),
+    }
+  }
+}
+
+// Records of a datasource, and how they are handed out to requests. The
+// cursor is shared by all the dispatcher threads.
+#[derive(Debug)]
+pub struct Pool {
+  pub mode: Mode,
+  pub exhausted: Exhausted,
+  pub columns: Vec<String>,
+  source: Box<dyn DataSource>,
+  // Order of the records in the shuffle mode, only allocated when shuffled
+  order: Vec<usize>,
+  cursor: AtomicUsize,
+}
+
+impl Pool {
+  pub fn new(source: Box<dyn DataSource>, mode: Mode, exhausted: Exhausted) -> Pool {
+    Pool {
+      mode,
+      exhausted,
+      columns: source.columns(),
+      source,
+      order: vec![],
+      cursor: AtomicUsize::new(0),
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    self.source.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.source.is_empty()
+  }
+
+  // Shuffle the order values are handed out in, for the shuffle mode.
+  pub fn shuffle<R: Rng>(&mut self, rng: &mut R) {
+    if self.mode == Mode::Shuffle {
+      self.order = (0..self.len()).collect();
+      self.order.shuffle(rng);
+    }
+  }
+
+  pub fn get(&self, idx: usize) -> Record {
+    self.source.get(idx).unwrap_or_default()
+  }
+
+  // Returns the index of the next record, or None if the pool is empty or, in
+  // the once mode, if all its records were already used.
+  pub fn next<R: Rng>(&self, rng: &mut R) -> Option<usize> {
+    if self.is_empty() {
+      return None;
+    }
+
+    let idx = match self.mode {
+      Mode::Random => rng.gen_range(0..self.len()),
+      Mode::Sequential => self.cursor.fetch_add(1, Ordering::SeqCst) % self.len(),
+      // Records are handed out in their natural order until the pool is shuffled
+      Mode::Shuffle => {
+        let idx = self.cursor.fetch_add(1, Ordering::SeqCst) % self.len();
+
+        self.order.get(idx).copied().unwrap_or(idx)
+      }
+      Mode::Once => {
+        let idx = self.cursor.fetch_add(1, Ordering::SeqCst);
+
+        if idx >= self.len() {
+          return None;
+        }
+
+        idx
+      }
+    };
+
+    Some(idx)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use rand::{rngs::StdRng, SeedableRng};
+  use std::thread;
+
+  use crate::datasource::Array;
+
+  fn pool(len: usize, mode: Mode) -> Pool {
+    let values = (0..len).map(|idx| idx.to_string()).collect::<Vec<_>>();
+
+    Pool::new(Box::new(Array::new(&values)), mode, Exhausted::Fail)
+  }
+
+  // Indexes handed out by `threads` threads calling `next` `calls` times each,
+  // with how many times each was handed out.
+  fn draw(pool: &Pool, threads: usize, calls: usize) -> (Vec<usize>, usize) {
+    let mut counts = vec![0; pool.len()];
+    let mut exhausted = 0;
+
+    thread::scope(|scope| {
+      let handles = (0..threads)
+        .map(|seed| {
+          scope.spawn(move || {
+            let mut rng = StdRng::seed_from_u64(seed as u64);
+
+            (0..calls).map(|_| pool.next(&mut rng)).collect::<Vec<_>>()
+          })
+        })
+        .collect::<Vec<_>>();
+
+      for handle in handles {
+        for idx in handle.join().unwrap() {
+          match idx {
+            Some(idx) => counts[idx] += 1,
+            None => exhausted += 1,
+          }
+        }
+      }
+    });
+
+    (counts, exhausted)
+  }
+
+  #[test]
+  fn once_hands_out_every_record_once_across_threads() {
+    let pool = pool(100, Mode::Once);
+    let (counts, exhausted) = draw(&pool, 8, 50);
+
+    assert!(counts.iter().all(|count| *count == 1));
+    assert_eq!(exhausted, 300);
+    assert_eq!(pool.next(&mut StdRng::seed_from_u64(0)), None);
+  }
+
+  #[test]
+  fn sequential_wraps_around_across_threads() {
+    let pool = pool(100, Mode::Sequential);
+    let (counts, exhausted) = draw(&pool, 4, 250);
+
+    assert!(counts.iter().all(|count| *count == 10));
+    assert_eq!(exhausted, 0);
+  }
+
+  #[test]
+  fn shuffle_hands_out_each_record_once_per_round() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut pool = pool(100, Mode::Shuffle);
+
+    pool.shuffle(&mut rng);
+
+    let round = (0..100).map(|_| pool.next(&mut rng).unwrap()).collect::<Vec<_>>();
+    let mut sorted = round.clone();
+
+    sorted.sort_unstable();
+    assert_eq!(sorted, (0..100).collect::<Vec<_>>());
+    assert_ne!(round, sorted);
+
+    let (counts, exhausted) = draw(&pool, 4, 100);
+
+    assert!(counts.iter().all(|count| *count == 4));
+    assert_eq!(exhausted, 0);
+  }
+
+  #[test]
+  fn unshuffled_pools_use_the_natural_order() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let pool = pool(3, Mode::Shuffle);
+
+    assert_eq!((0..4).map(|_| pool.next(&mut rng)).collect::<Vec<_>>(), vec![Some(0), Some(1), Some(2), Some(0)]);
+  }
+
+  #[test]
+  fn empty_pools_hand_out_nothing() {
+    let mut rng = StdRng::seed_from_u64(0);
+
+    for mode in [Mode::Random, Mode::Sequential, Mode::Once, Mode::Shuffle] {
+      assert_eq!(pool(0, mode).next(&mut rng), None);
+    }
+  }
+}
@@ -0,0 +1,104 @@
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+const FIRST_NAMES: &[&str] = &[
+  "james",
+  "mary",
+  "robert",
+  "patricia",
+  "john",
+  "jennifer",
+  "michael",
+  "linda",
+  "david",
+  "elizabeth",
+  "william",
+  "barbara",
+  "richard",
+  "susan",
+  "joseph",
+  "jessica",
+  "thomas",
+  "sarah",
+  "charles",
+  "karen",
+  "antoine",
+  "camille",
+  "lucas",
+  "chloe",
+  "hugo",
+  "emma",
+  "louis",
+  "lea",
+  "jules",
+  "manon",
+];
+
+const LAST_NAMES: &[&str] = &[
+  "smith",
+  "johnson",
+  "williams",
+  "brown",
+  "jones",
+  "garcia",
+  "miller",
+  "davis",
+  "rodriguez",
+  "martinez",
+  "hernandez",
+  "lopez",
+  "gonzalez",
+  "wilson",
+  "anderson",
+  "thomas",
+  "taylor",
+  "moore",
+  "jackson",
+  "martin",
+  "bernard",
+  "dubois",
+  "durand",
+  "lefebvre",
+  "leroy",
+  "moreau",
+  "simon",
+  "laurent",
+  "michel",
+  "garnier",
+];
+
+const WORDS: &[&str] = &[
+  "alpha", "bravo", "charlie", "delta", "echo", "foxtrot", "golf", "hotel", "india", "juliet", "kilo", "lima", "mike", "november", "oscar", "papa", "quebec", "romeo", "sierra", "tango", "uniform",
+  "victor", "whiskey", "xray", "yankee", "zulu",
+];
+
+const DOMAINS: &[&str] = &["example.com", "example.net", "example.org", "mail.example.com", "test.example"];
+
+pub const KINDS: &[&str] = &["first_name", "last_name", "name", "username", "email", "word", "domain", "phone"];
+
+fn pick<R: Rng>(list: &[&str], rng: &mut R) -> String {
+  list.choose(rng).unwrap().to_string()
+}
+
+fn capitalize(value: &str) -> String {
+  let mut chars = value.chars();
+
+  match chars.next() {
+    Some(first) => first.to_uppercase().chain(chars).collect(),
+    None => String::new(),
+  }
+}
+
+pub fn generate<R: Rng>(kind: &str, rng: &mut R) -> String {
+  match kind {
+    "first_name" => capitalize(&pick(FIRST_NAMES, rng)),
+    "last_name" => capitalize(&pick(LAST_NAMES, rng)),
+    "name" => format!("{} {}", capitalize(&pick(FIRST_NAMES, rng)), capitalize(&pick(LAST_NAMES, rng))),
+    "username" => format!("{}{}", pick(FIRST_NAMES, rng), rng.gen_range(1..10000)),
+    "email" => format!("{}.{}{}@{}", pick(FIRST_NAMES, rng), pick(LAST_NAMES, rng), rng.gen_range(1..1000), pick(DOMAINS, rng)),
+    "word" => pick(WORDS, rng),
+    "domain" => pick(DOMAINS, rng),
+    "phone" => format!("+1555{:07}", rng.gen_range(0..10_000_000)),
+    _ => String::new(),
+  }
+}
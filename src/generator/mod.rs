@@ -0,0 +1,176 @@
+mod faker;
+
+use chrono::prelude::*;
+use rand::distributions::Alphanumeric;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Values computed for every request rather than drawn from a datasource, like
+// `{uuid}` or `{randint(1,100)}`. Random generators use the request's rng so
+// a seeded run produces the same values.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Generator {
+  Uuid,
+  RandInt(i64, i64),
+  RandStr(usize),
+  Timestamp,
+  NowIso,
+  Faker(String),
+  Seq,
+  Choice(Vec<String>),
+}
+
+pub const NAMES: &[&str] = &["uuid", "randint", "randstr", "timestamp", "now_iso", "faker", "seq", "choice"];
+
+fn arguments(args: Option<&str>) -> Vec<&str> {
+  match args {
+    Some(args) if !args.trim().is_empty() => args.split(',').map(str::trim).collect(),
+    _ => vec![],
+  }
+}
+
+impl Generator {
+  // Builds the generator of a placeholder from its name, field and arguments,
+  // as in `{faker.email}` or `{choice(a,b)}`.
+  pub fn parse(name: &str, field: Option<&str>, args: Option<&str>) -> Result<Generator, String> {
+    let arguments = arguments(args);
+
+    match (name, field) {
+      ("faker", Some(kind)) if args.is_none() => {
+        if !faker::KINDS.contains(&kind) {
+          return Err(format!("unknown faker '{}', expected one of {}", kind, faker::KINDS.join(", ")));
+        }
+
+        Ok(Generator::Faker(kind.to_owned()))
+      }
+      ("faker", _) => Err(format!("generator 'faker' requires a kind, one of {}", faker::KINDS.join(", "))),
+      (_, Some(_)) => Err(format!("generator '{}' has no fields", name)),
+
+      ("uuid", None) | ("timestamp", None) | ("now_iso", None) | ("seq", None) if !arguments.is_empty() => Err(format!("generator '{}' takes no arguments", name)),
+      ("uuid", None) => Ok(Generator::Uuid),
+      ("timestamp", None) => Ok(Generator::Timestamp),
+      ("now_iso", None) => Ok(Generator::NowIso),
+      ("seq", None) => Ok(Generator::Seq),
+
+      ("randint", None) => match arguments.iter().map(|arg| arg.parse::<i64>()).collect::<Result<Vec<i64>, _>>() {
+        Ok(ref bounds) if bounds.len() == 2 && bounds[0] <= bounds[1] => Ok(Generator::RandInt(bounds[0], bounds[1])),
+        _ => Err("generator 'randint' expects two integers, as in 'randint(1,100)'".to_owned()),
+      },
+
+      ("randstr", None) => match arguments.as_slice() {
+        [length] => length
+          .parse::<usize>()
+          .map(Generator::RandStr)
+          .map_err(|_| format!("invalid length '{}' for generator 'randstr'", length)),
+        _ => Err("generator 'randstr' expects a length, as in 'randstr(16)'".to_owned()),
+      },
+
+      ("choice", None) if !arguments.is_empty() => Ok(Generator::Choice(arguments.iter().map(|arg| (*arg).to_owned()).collect())),
+      ("choice", None) => Err("generator 'choice' expects at least one value, as in 'choice(a,b)'".to_owned()),
+
+      _ => Err(format!("unknown generator '{}'", name)),
+    }
+  }
+
+  pub fn generate<R: Rng>(&self, sequence: &AtomicU64, rng: &mut R) -> String {
+    match self {
+      Generator::Uuid => uuid(rng),
+      Generator::RandInt(from, to) => rng.gen_range(*from..=*to).to_string(),
+      Generator::RandStr(length) => (0..*length).map(|_| char::from(rng.sample(Alphanumeric))).collect(),
+      Generator::Timestamp => Utc::now().timestamp().to_string(),
+      Generator::NowIso => Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+      Generator::Faker(kind) => faker::generate(kind, rng),
+      Generator::Seq => (sequence.fetch_add(1, Ordering::SeqCst) + 1).to_string(),
+      Generator::Choice(values) => values.choose(rng).unwrap().clone(),
+    }
+  }
+}
+
+// Random (version 4) UUID.
+fn uuid<R: Rng>(rng: &mut R) -> String {
+  let mut bytes: [u8; 16] = rng.gen();
+
+  bytes[6] = (bytes[6] & 0x0f) | 0x40;
+  bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+  let hex = bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<String>();
+
+  format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use rand::rngs::StdRng;
+  use rand::SeedableRng;
+
+  fn generate(name: &str, field: Option<&str>, args: Option<&str>) -> String {
+    Generator::parse(name, field, args).unwrap().generate(&AtomicU64::new(0), &mut StdRng::seed_from_u64(0))
+  }
+
+  #[test]
+  fn parses_generators() {
+    assert_eq!(Generator::parse("randint", None, Some("1, 10")), Ok(Generator::RandInt(1, 10)));
+    assert_eq!(Generator::parse("randstr", None, Some("16")), Ok(Generator::RandStr(16)));
+    assert_eq!(Generator::parse("choice", None, Some("a,b")), Ok(Generator::Choice(vec!["a".to_owned(), "b".to_owned()])));
+    assert_eq!(Generator::parse("faker", Some("email"), None), Ok(Generator::Faker("email".to_owned())));
+    assert_eq!(Generator::parse("seq", None, Some(" ")), Ok(Generator::Seq));
+  }
+
+  #[test]
+  fn rejects_invalid_arguments() {
+    assert_eq!(
+      Generator::parse("randint", None, Some("10,1")),
+      Err("generator 'randint' expects two integers, as in 'randint(1,100)'".to_owned())
+    );
+    assert_eq!(Generator::parse("randstr", None, Some("x")), Err("invalid length 'x' for generator 'randstr'".to_owned()));
+    assert_eq!(Generator::parse("uuid", None, Some("1")), Err("generator 'uuid' takes no arguments".to_owned()));
+    assert_eq!(
+      Generator::parse("choice", None, None),
+      Err("generator 'choice' expects at least one value, as in 'choice(a,b)'".to_owned())
+    );
+    assert_eq!(Generator::parse("seq", Some("next"), None), Err("generator 'seq' has no fields".to_owned()));
+    assert!(Generator::parse("faker", Some("color"), None).unwrap_err().starts_with("unknown faker 'color'"));
+    assert_eq!(Generator::parse("nope", None, None), Err("unknown generator 'nope'".to_owned()));
+  }
+
+  #[test]
+  fn generates_values_within_their_bounds() {
+    let value: i64 = generate("randint", None, Some("5,7")).parse().unwrap();
+
+    assert!((5..=7).contains(&value));
+    assert_eq!(generate("randint", None, Some("3,3")), "3");
+    assert_eq!(generate("randstr", None, Some("12")).len(), 12);
+    assert!(generate("randstr", None, Some("12")).chars().all(|character| character.is_ascii_alphanumeric()));
+    assert_eq!(generate("choice", None, Some("only")), "only");
+  }
+
+  #[test]
+  fn generates_version_4_uuids() {
+    let uuid = generate("uuid", None, None);
+    let groups: Vec<&str> = uuid.split('-').collect();
+
+    assert_eq!(groups.iter().map(|group| group.len()).collect::<Vec<_>>(), vec![8, 4, 4, 4, 12]);
+    assert!(groups[2].starts_with('4'));
+    assert!(matches!(groups[3].chars().next(), Some('8' | '9' | 'a' | 'b')));
+  }
+
+  #[test]
+  fn seeded_generators_repeat_their_values() {
+    assert_eq!(generate("uuid", None, None), generate("uuid", None, None));
+    assert_eq!(generate("faker", Some("name"), None), generate("faker", Some("name"), None));
+  }
+
+  #[test]
+  fn sequences_are_shared() {
+    let sequence = AtomicU64::new(0);
+    let mut rng = StdRng::seed_from_u64(0);
+
+    let values: Vec<String> = (0..3).map(|_| Generator::Seq.generate(&sequence, &mut rng)).collect();
+
+    assert_eq!(values, vec!["1", "2", "3"]);
+  }
+}
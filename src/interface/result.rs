@@ -1,6 +1,8 @@
 use colored::*;
+use std::sync::atomic::Ordering;
 use std::sync::{Arc, Mutex};
 
+use crate::specs::{ConnectionStats, Distribution};
 use crate::util;
 
 pub enum State {
@@ -14,11 +16,21 @@ pub struct Success {
   pub request: String,
   pub duration: i64,
   pub code: u16,
+  pub protocol: &'static str,
+  // Bytes of the response body which were read
+  pub bytes: u64,
 }
 
 impl Success {
-  pub fn new(start: u64, request: String, duration: i64, code: u16) -> Result<Success, Failure> {
-    Ok(Success { start, request, duration, code })
+  pub fn new(start: u64, request: String, duration: i64, code: u16, protocol: &'static str, bytes: u64) -> Result<Success, Failure> {
+    Ok(Success {
+      start,
+      request,
+      duration,
+      code,
+      protocol,
+      bytes,
+    })
   }
 }
 
@@ -27,16 +39,22 @@ pub struct Failure {
   pub request: String,
   pub duration: i64,
   pub code: u16,
+  // Empty when no response was received
+  pub protocol: &'static str,
+  pub bytes: u64,
   pub reason: String,
 }
 
 impl Failure {
-  pub fn http(start: u64, request: String, duration: i64, code: u16, reason: String) -> Result<Success, Failure> {
+  #[allow(clippy::too_many_arguments)]
+  pub fn http(start: u64, request: String, duration: i64, code: u16, protocol: &'static str, bytes: u64, reason: String) -> Result<Success, Failure> {
     Err(Failure {
       start,
       request,
       duration,
       code,
+      protocol,
+      bytes,
       reason,
     })
   }
@@ -47,12 +65,14 @@ impl Failure {
       request,
       duration,
       code: 0,
+      protocol: "",
+      bytes: 0,
       reason,
     })
   }
 }
 
-pub fn process(results: &Arc<Mutex<Vec<Result<Success, Failure>>>>) {
+pub fn process(results: &Arc<Mutex<Vec<Result<Success, Failure>>>>, connections: &ConnectionStats, distribution: &Distribution) {
   let data = results.lock().unwrap();
   let success = data
     .iter()
@@ -74,7 +94,7 @@ pub fn process(results: &Arc<Mutex<Vec<Result<Success, Failure>>>>) {
   }
 
   if histogram.entries() > 0 {
-    let kv = vec![
+    let mut kv = vec![
       ("Requests count", format!("{}", data.len())),
       ("Success count:", format!("{}", success.len())),
       ("Error count:", format!("{}", data.len() - success.len())),
@@ -86,17 +106,112 @@ pub fn process(results: &Arc<Mutex<Vec<Result<Success, Failure>>>>) {
       ("99th percentile:", format_res_ms(histogram.percentile(99.0))),
     ];
 
+    // Only shown when response bodies are read
+    let received = data
+      .iter()
+      .map(|result| match result {
+        Ok(Success { bytes, .. }) | Err(Failure { bytes, .. }) => *bytes,
+      })
+      .sum::<u64>();
+
+    if received > 0 {
+      kv.push(("Bytes received:", format_bytes(received)));
+    }
+
     println!("{}", "STATISTICS:".blue().bold());
     util::print_kv(kv);
+    println!();
   } else {
     util::info("no results");
   }
+
+  let distribution = picks(distribution);
+
+  if !distribution.is_empty() {
+    println!("{}", "DISTRIBUTION:".blue().bold());
+    util::print_kv(distribution);
+  }
+
+  println!();
+  println!("{}", "CONNECTIONS:".blue().bold());
+  util::print_kv(vec![
+    ("Opened:", connections.opened.load(Ordering::SeqCst).to_string()),
+    ("Reused:", connections.reused.load(Ordering::SeqCst).to_string()),
+    ("Failed:", connections.failed.load(Ordering::SeqCst).to_string()),
+  ]);
+
+  let protocols = protocols(&data);
+
+  if !protocols.is_empty() {
+    println!();
+    println!("{}", "PROTOCOLS:".blue().bold());
+    util::print_kv(protocols);
+  }
+}
+
+// Number and share of the picks of each upstream, next to the share intended
+// by the weights. A WebSocket session is a single pick, whatever the number
+// of results it produces.
+fn picks(distribution: &Distribution) -> Vec<(&str, String)> {
+  let mut picks = distribution.picks();
+  let total = picks.iter().map(|(_, picks)| picks.actual).sum::<u64>() as usize;
+
+  if total == 0 {
+    return vec![];
+  }
+
+  picks.retain(|(_, picks)| picks.actual > 0 || picks.expected > 0.0);
+  picks.sort_by_key(|(_, picks)| std::cmp::Reverse(picks.actual));
+  picks
+    .into_iter()
+    .map(|(name, picks)| {
+      let actual = format_rate(picks.actual as usize, total);
+      let expected = picks.expected * 100.0 / total as f64;
+
+      (name, format!("{} ({:.2}%, intended {:.2}%)", picks.actual, actual, expected))
+    })
+    .collect()
+}
+
+// Number and share of responses received with each HTTP version.
+fn protocols(data: &[Result<Success, Failure>]) -> Vec<(&str, String)> {
+  let mut counts: Vec<(&str, usize)> = vec![];
+
+  for result in data {
+    let protocol = match result {
+      Ok(Success { protocol, .. }) => protocol,
+      Err(Failure { protocol, .. }) => protocol,
+    };
+
+    if protocol.is_empty() {
+      continue;
+    }
+
+    match counts.iter_mut().find(|(name, _)| name == protocol) {
+      Some((_, count)) => *count += 1,
+      None => counts.push((protocol, 1)),
+    }
+  }
+
+  let total = counts.iter().map(|(_, count)| count).sum();
+
+  counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+  counts.into_iter().map(|(name, count)| (name, format!("{} ({:.2}%)", count, format_rate(count, total)))).collect()
 }
 
 fn format_rate(value: usize, total: usize) -> f64 {
   value as f64 / total as f64 * 100.0
 }
 
+fn format_bytes(value: u64) -> String {
+  match value {
+    0..=1023 => format!("{}B", value),
+    1024..=1_048_575 => format!("{:.2}KiB", value as f64 / 1024.0),
+    1_048_576..=1_073_741_823 => format!("{:.2}MiB", value as f64 / 1_048_576.0),
+    _ => format!("{:.2}GiB", value as f64 / 1_073_741_824.0),
+  }
+}
+
 fn format_res_ms(value: Result<u64, &'static str>) -> String {
   match value {
     Ok(value) => {
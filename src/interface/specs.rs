@@ -1,9 +1,22 @@
 use colored::*;
-use std::collections::HashMap;
+use http::uri::PathAndQuery;
+use prost_reflect::{DescriptorPool, MethodDescriptor};
+use regex::Regex;
+use reqwest::blocking::Client;
+use reqwest::cookie::Jar;
+use reqwest::tls::{Certificate, Identity, Version};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
+use tokio::runtime::Runtime;
+use tonic::transport::{Channel, ClientTlsConfig};
 
-use crate::datasource::Data;
+use crate::datasource::Pool;
 use crate::scheduler::*;
+use crate::template::{JsonTemplate, Template};
 
 #[derive(Debug)]
 pub enum HttpMethod {
@@ -28,24 +41,186 @@ pub struct Scenario {
   pub start: f64,
   pub upstreams: Vec<Upstream>,
   pub schedulers: Vec<Scheduler>,
-  pub datasources: HashMap<String, Data>,
+  pub datasources: HashMap<String, Pool>,
+  // Set when the scenario must end before its schedulers are done
+  pub stopped: AtomicBool,
+  // Counter behind the `{seq}` generator
+  pub sequence: AtomicU64,
+  pub users: Vec<VirtualUser>,
+  pub next_user: AtomicUsize,
+  pub connections: Arc<ConnectionStats>,
+  pub distribution: Arc<Distribution>,
+}
+
+impl Scenario {
+  // Requests are handed to the virtual users in turn, if there are any.
+  pub fn next_user(&self) -> Option<usize> {
+    match self.users.len() {
+      0 => None,
+      count => Some(self.next_user.fetch_add(1, Ordering::SeqCst) % count),
+    }
+  }
+
+  // How long the last requests may take to complete once sent: the timeout of
+  // requests, or the length of the longest WebSocket session.
+  pub fn drain(&self) -> u64 {
+    self
+      .upstreams
+      .iter()
+      .filter_map(|upstream| match upstream.kind {
+        Kind::Websocket(ref websocket) => Some(websocket.length(self.options.timeout)),
+        _ => None,
+      })
+      .fold(self.options.timeout, u64::max)
+  }
 }
 
 #[derive(Debug)]
 pub struct Options {
   pub timeout: u64,
+  pub cookies: bool,
+  pub virtual_users: u64,
+  pub connections: Connections,
 }
 
 impl Default for Options {
   fn default() -> Self {
-    Self { timeout: 5 }
+    Self {
+      timeout: 5,
+      cookies: false,
+      virtual_users: 1,
+      connections: Connections::default(),
+    }
+  }
+}
+
+// How connections are kept open between requests, each upstream and virtual
+// user having its own pool of connections.
+#[derive(Debug)]
+pub struct Connections {
+  pub reuse: bool,
+  // Idle connections kept per host
+  pub pool_size: Option<usize>,
+  // Requests sent through a pool before its connections are replaced
+  pub max_requests: Option<u64>,
+  pub idle_timeout: Option<u64>,
+}
+
+impl Default for Connections {
+  fn default() -> Self {
+    Self {
+      reuse: true,
+      pool_size: None,
+      max_requests: None,
+      idle_timeout: None,
+    }
+  }
+}
+
+// Connections opened during the run, those which could not be, and the
+// requests sent over a connection already used by an earlier request.
+#[derive(Debug, Default)]
+pub struct ConnectionStats {
+  pub opened: AtomicU64,
+  pub failed: AtomicU64,
+  pub reused: AtomicU64,
+  // Local and remote addresses of the HTTP connections not used yet
+  pub fresh: Mutex<HashSet<(SocketAddr, SocketAddr)>>,
+}
+
+// Upstreams picked by the schedulers during the run, and how many times each
+// should have been picked according to the weights in effect for every pick.
+#[derive(Debug, Default)]
+pub struct Distribution {
+  names: Vec<String>,
+  picks: Mutex<Vec<Picks>>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Picks {
+  pub actual: u64,
+  pub expected: f64,
+}
+
+impl Distribution {
+  pub fn new(names: Vec<String>) -> Distribution {
+    Distribution {
+      picks: Mutex::new(vec![Picks::default(); names.len()]),
+      names,
+    }
+  }
+
+  // Record the pick of an upstream, `shares` being the share of the picks each
+  // upstream was meant to receive.
+  pub fn record(&self, upstream: usize, shares: &[f64]) {
+    let mut picks = self.picks.lock().unwrap();
+
+    if let Some(picked) = picks.get_mut(upstream) {
+      picked.actual += 1;
+    }
+
+    for (picks, share) in picks.iter_mut().zip(shares) {
+      picks.expected += share;
+    }
+  }
+
+  // Picks of every upstream, by name.
+  pub fn picks(&self) -> Vec<(&str, Picks)> {
+    self.names.iter().map(String::as_str).zip(self.picks.lock().unwrap().iter().copied()).collect()
   }
 }
 
+// Clients are kept for each virtual user and source address.
+pub type PoolKey = (Option<usize>, Option<IpAddr>);
+
+// Client of an upstream for a virtual user, with the number of requests sent
+// through its connections.
+
+#[derive(Debug)]
+pub struct ClientPool {
+  pub client: Client,
+  pub requests: u64,
+}
+
+impl fmt::Display for Connections {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    if !self.reuse {
+      return write!(f, "{}", "new connection for every request".bold());
+    }
+
+    write!(f, "{}", "reused".bold())?;
+
+    if let Some(pool_size) = self.pool_size {
+      write!(f, ", {} idle per host", pool_size)?;
+    }
+    if let Some(max_requests) = self.max_requests {
+      write!(f, ", renewed every {} requests", max_requests)?;
+    }
+    if let Some(idle_timeout) = self.idle_timeout {
+      write!(f, ", closed after {}s idle", idle_timeout)?;
+    }
+
+    Ok(())
+  }
+}
+
+// A virtual user keeps the cookies set by the responses to its requests, and
+// sends them back with the following ones.
+#[derive(Debug, Default)]
+pub struct VirtualUser {
+  pub jar: Arc<Jar>,
+}
+
 impl fmt::Display for Scenario {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     writeln!(f, "{}", "OPTIONS".blue().bold())?;
     writeln!(f, "  {} {}", "timeout:".dimmed(), format!("{}s", self.options.timeout).bold())?;
+
+    if self.options.cookies {
+      writeln!(f, "  {} {} with cookies", "virtual users:".dimmed(), self.options.virtual_users.to_string().bold())?;
+    }
+
+    writeln!(f, "  {} {}", "connections:".dimmed(), self.options.connections)?;
     writeln!(f)?;
 
     writeln!(f, "{}", "SCHEDULERS:".blue().bold())?;
@@ -60,7 +235,7 @@ impl fmt::Display for Scenario {
 
     writeln!(f, "{}", "DATASOURCES:".blue().bold())?;
     for (name, data) in &self.datasources {
-      writeln!(f, "  - {}: {} entries", name.bold(), data.len())?;
+      writeln!(f, "  - {}: {} entries ({})", name.bold(), data.len(), data.mode)?;
     }
 
     Ok(())
@@ -70,17 +245,37 @@ impl fmt::Display for Scenario {
 #[derive(Debug)]
 pub struct Upstream {
   pub name: String,
+  pub kind: Kind,
   pub method: HttpMethod,
-  pub url: String,
-  pub headers: HashMap<String, String>,
+  pub url: Template,
+  pub weight: f64,
+  pub headers: BTreeMap<String, Template>,
   pub basic: Option<UpstreamBasicAuth>,
-  pub body: Option<String>,
+  // Identical authentications are shared by the upstreams using them
+  pub auth: Option<Arc<Auth>>,
+  pub signing: Option<Signing>,
+  pub tls: Option<Tls>,
+  pub http: Http,
+  pub network: Network,
+  pub read_body: ReadBody,
+  // Clients are built when first needed
+  pub pools: Mutex<HashMap<PoolKey, ClientPool>>,
+  pub body: Option<Body>,
 }
 
 impl fmt::Display for Upstream {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     writeln!(f, "  - {} {}", "name:".dimmed(), self.name.bold())?;
-    writeln!(f, "    {} {}", self.method, self.url)?;
+    match self.kind {
+      Kind::Http => writeln!(f, "    {} {}", self.method, self.url)?,
+      Kind::Websocket(ref websocket) => write!(f, "    {} {}\n{}", "WEBSOCKET".bold(), self.url, websocket)?,
+      Kind::Grpc(ref grpc) => write!(f, "    {} {} {}\n{}", "GRPC".bold(), self.url, grpc.path, grpc)?,
+      Kind::Socket(ref socket) => write!(f, "    {} {}\n{}", socket.transport.to_string().bold(), self.url, socket)?,
+    }
+
+    if (self.weight - 1.0).abs() > f64::EPSILON {
+      writeln!(f, "    Weight: {}", self.weight)?;
+    }
 
     if !self.headers.is_empty() {
       writeln!(f, "    Headers:")?;
@@ -89,12 +284,570 @@ impl fmt::Display for Upstream {
       }
     }
 
+    if let Some(ref basic) = self.basic {
+      writeln!(f, "    Basic auth: {}", basic.username.bold())?;
+    }
+
+    if let Some(ref auth) = self.auth {
+      writeln!(f, "    Auth: {}", auth.kind)?;
+    }
+
+    if let Some(ref signing) = self.signing {
+      writeln!(f, "    Signing: {}", signing)?;
+    }
+
+    if let Some(ref tls) = self.tls {
+      writeln!(f, "    TLS: {}", tls)?;
+    }
+
+    if let Some(protocol) = self.http.protocol {
+      writeln!(f, "    Protocol: {}", protocol)?;
+    }
+
+    match self.http.redirects {
+      0 => writeln!(f, "    Redirects: not followed")?,
+      REDIRECTS => {}
+      redirects => writeln!(f, "    Redirects: up to {}", redirects)?,
+    }
+
+    if !self.http.decompress.is_empty() {
+      writeln!(f, "    Decompression: {}", self.http.decompress.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))?;
+    }
+
+    match self.read_body {
+      ReadBody::Discard => {}
+      ReadBody::Full => writeln!(f, "    Response body: read fully")?,
+      ReadBody::Limit(limit) => writeln!(f, "    Response body: first {} bytes", limit)?,
+    }
+
+    if !self.network.is_empty() {
+      writeln!(f, "    Network: {}", self.network)?;
+    }
+
+    if let Some(ref body) = self.body {
+      write!(f, "{}", body)?;
+    }
+
+    Ok(())
+  }
+}
+
+// What the requests of an upstream are.
+#[derive(Debug)]
+pub enum Kind {
+  Http,
+  Websocket(Websocket),
+  Grpc(Box<Grpc>),
+  Socket(Socket),
+}
+
+// Session opened by a request to a WebSocket upstream, sending its messages in
+// turn, `repeat` times, then closing the connection.
+#[derive(Debug)]
+pub struct Websocket {
+  pub messages: Vec<Message>,
+  pub interval: u64,
+  pub repeat: u64,
+  // Connector of `wss://` URLs with the TLS settings of the upstream, and the
+  // server name sent instead of the host of the URL
+  pub tls: Option<native_tls::TlsConnector>,
+  pub sni: Option<String>,
+}
+
+// Message sent during a WebSocket session, optionally waiting for a reply
+// matching `expect` to measure its round-trip time.
+#[derive(Debug)]
+pub struct Message {
+  pub send: Template,
+  pub expect: Option<Regex>,
+  pub timeout: u64,
+}
+
+impl Websocket {
+  // Longest time a session can last, connecting and closing included.
+  pub fn length(&self, timeout: u64) -> u64 {
+    let messages = self.messages.len() as u64 * self.repeat;
+    let replies = self.messages.iter().filter(|message| message.expect.is_some()).map(|message| message.timeout).sum::<u64>() * self.repeat;
+
+    2 * timeout + replies + messages.saturating_sub(1) * self.interval
+  }
+}
+
+impl fmt::Display for Websocket {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    if self.interval > 0 {
+      writeln!(f, "    Interval: {}s between messages", self.interval)?;
+    }
+
+    if self.repeat > 1 {
+      writeln!(f, "    Repeat: {} times", self.repeat)?;
+    }
+
+    if !self.messages.is_empty() {
+      writeln!(f, "    Messages:")?;
+      for message in &self.messages {
+        match message.expect {
+          Some(ref expect) => writeln!(f, "      - {} {} {} (within {}s)", message.send, "→".dimmed(), expect.as_str().bold(), message.timeout)?,
+          None => writeln!(f, "      - {}", message.send)?,
+        }
+      }
+    }
+
     Ok(())
   }
 }
 
+// Unary calls to a gRPC method, sending the JSON body of the upstream as their
+// message. Without a proto file, the method is described by the server through
+// reflection, before the first call.
+#[derive(Debug)]
+pub struct Grpc {
+  pub service: String,
+  pub method: String,
+  // Path of the calls, as `/package.Service/Method`
+  pub path: PathAndQuery,
+  pub proto: Option<String>,
+  pub descriptor: Mutex<Option<MethodDescriptor>>,
+  pub tls: Option<ClientTlsConfig>,
+  // Calls are made from a runtime shared by the requests of the upstream,
+  // started with the first one
+  pub runtime: OnceLock<Runtime>,
+  // Channels multiplex the calls of every virtual user over their connection
+  pub channels: Mutex<HashMap<(String, Option<IpAddr>), ChannelPool>>,
+}
+
+// Channel to a gRPC server, with the number of calls sent through it.
+#[derive(Debug)]
+pub struct ChannelPool {
+  pub channel: Channel,
+  pub requests: u64,
+}
+
+impl Grpc {
+  // Unary method of a service, among the files of a pool.
+  pub fn find(pool: &DescriptorPool, service: &str, method: &str) -> Result<MethodDescriptor, String> {
+    let service = pool.get_service_by_name(service).ok_or_else(|| format!("unknown service '{}'", service))?;
+    let method = service
+      .methods()
+      .find(|candidate| candidate.name() == method)
+      .ok_or_else(|| format!("unknown method '{}' in service '{}'", method, service.full_name()))?;
+
+    if method.is_client_streaming() || method.is_server_streaming() {
+      return Err(format!("method '{}' is streaming, only unary methods are supported", method.full_name()));
+    }
+
+    Ok(method)
+  }
+}
+
+impl fmt::Display for Grpc {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self.proto {
+      Some(ref proto) => writeln!(f, "    Proto: {}", proto),
+      None => writeln!(f, "    Proto: from server reflection"),
+    }
+  }
+}
+
+// Payload sent on a new TCP connection, or in a UDP datagram, for each request,
+// followed by the reply if one is read.
+#[derive(Debug)]
+pub struct Socket {
+  pub transport: Transport,
+  pub payload: Template,
+  // The payload is rendered then decoded from hexadecimal
+  pub hex: bool,
+  pub reply: Reply,
+  pub expect: Option<regex::bytes::Regex>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transport {
+  Tcp,
+  Udp,
+}
+
+// How much of the reply is read before a request is complete, from the data
+// received on a TCP connection or from as many datagrams as needed.
+#[derive(Debug)]
+pub enum Reply {
+  Ignore,
+  Delimiter(Vec<u8>),
+  Bytes(u64),
+}
+
+impl Socket {
+  // Bytes of a hexadecimal payload, whose digits can be separated by whitespace.
+  pub fn decode(payload: &[u8]) -> Result<Vec<u8>, hex::FromHexError> {
+    hex::decode(payload.iter().filter(|byte| !byte.is_ascii_whitespace()).copied().collect::<Vec<_>>())
+  }
+}
+
+impl fmt::Display for Transport {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Transport::Tcp => write!(f, "TCP"),
+      Transport::Udp => write!(f, "UDP"),
+    }
+  }
+}
+
+impl fmt::Display for Socket {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    // Payloads often end with line breaks, which are shown escaped
+    match self.hex {
+      true => writeln!(f, "    Payload: {} (hex)", self.payload)?,
+      false => writeln!(f, "    Payload: {:?}", self.payload.to_string())?,
+    }
+
+    match self.reply {
+      Reply::Ignore => {}
+      Reply::Delimiter(ref delimiter) => writeln!(f, "    Reply: until {}", format!("{:?}", String::from_utf8_lossy(delimiter)).bold())?,
+      Reply::Bytes(bytes) => writeln!(f, "    Reply: {} bytes", bytes)?,
+    }
+
+    if let Some(ref expect) = self.expect {
+      writeln!(f, "    Expect: {}", expect.as_str().bold())?;
+    }
+
+    Ok(())
+  }
+}
+
+#[derive(Debug)]
+pub enum Body {
+  Text(Template),
+  Raw(Vec<u8>),
+  Form(BTreeMap<String, Template>),
+  Multipart(BTreeMap<String, Part>),
+  Json(JsonTemplate),
+}
+
+#[derive(Debug)]
+pub enum Part {
+  Text(Template),
+  File(FilePart),
+}
+
+// File sent in a multipart field, read for every request.
+#[derive(Debug)]
+pub struct FilePart {
+  pub path: String,
+  pub filename: Option<String>,
+  pub content_type: Option<String>,
+}
+
+impl fmt::Display for Body {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Body::Text(body) => writeln!(f, "    Body: {}", body),
+      Body::Raw(body) => writeln!(f, "    Body: {} bytes", body.len()),
+      Body::Json(body) => writeln!(f, "    JSON: {}", body),
+
+      Body::Form(fields) => {
+        writeln!(f, "    Form:")?;
+        for (key, value) in fields {
+          writeln!(f, "      - {} = {}", key.bold(), value)?;
+        }
+
+        Ok(())
+      }
+
+      Body::Multipart(parts) => {
+        writeln!(f, "    Multipart:")?;
+        for (key, part) in parts {
+          match part {
+            Part::Text(value) => writeln!(f, "      - {} = {}", key.bold(), value)?,
+            Part::File(file) => writeln!(f, "      - {} = file {}", key.bold(), file.path)?,
+          }
+        }
+
+        Ok(())
+      }
+    }
+  }
+}
+
 #[derive(Debug)]
 pub struct UpstreamBasicAuth {
   pub username: String,
   pub password: String,
 }
+
+// Token added to the requests of the upstreams using an authentication, kept
+// until it must be renewed.
+#[derive(Debug)]
+pub struct Auth {
+  pub kind: AuthKind,
+  pub token: Mutex<Option<Token>>,
+}
+
+#[derive(Debug)]
+pub enum AuthKind {
+  Bearer(String),
+  OAuth2 {
+    token_url: String,
+    client_id: String,
+    client_secret: String,
+    scope: Option<String>,
+    refresh_before: u64,
+  },
+  // The token is read from the JSON response to an upstream, at `token`, and
+  // expires after the number of seconds at `expires_in` or after `ttl`.
+  Login {
+    upstream: usize,
+    token: String,
+    expires_in: Option<String>,
+    ttl: Option<u64>,
+    refresh_before: u64,
+  },
+}
+
+#[derive(Debug, Clone)]
+pub struct Token {
+  pub value: String,
+  pub renew: Option<Instant>,
+}
+
+impl Auth {
+  pub fn new(kind: AuthKind) -> Auth {
+    Auth { kind, token: Mutex::new(None) }
+  }
+}
+
+impl fmt::Display for AuthKind {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      AuthKind::Bearer(_) => write!(f, "bearer token"),
+      AuthKind::OAuth2 { token_url, client_id, .. } => write!(f, "OAuth2 client {} from {}", client_id.bold(), token_url),
+      AuthKind::Login { token, .. } => write!(f, "token {} from a login upstream", token.bold()),
+    }
+  }
+}
+
+// Signature added to the requests of an upstream once they are built.
+#[derive(Debug)]
+pub enum Signing {
+  // HMAC of the components of the request, one per line
+  Hmac {
+    secret: String,
+    algorithm: HmacAlgorithm,
+    header: String,
+    prefix: String,
+    base64: bool,
+    components: Vec<Component>,
+  },
+  AwsSigv4 {
+    access_key: String,
+    secret_key: String,
+    region: String,
+    service: String,
+    session_token: Option<String>,
+  },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum HmacAlgorithm {
+  Sha256,
+  Sha512,
+}
+
+#[derive(Debug)]
+pub enum Component {
+  Method,
+  Path,
+  Date,
+  Body,
+  Header(String),
+}
+
+impl Component {
+  pub fn parse(name: &str) -> Option<Component> {
+    match name {
+      "method" => Some(Component::Method),
+      "path" => Some(Component::Path),
+      "date" => Some(Component::Date),
+      "body" => Some(Component::Body),
+      _ => name.strip_prefix("header:").filter(|header| !header.is_empty()).map(|header| Component::Header(header.to_owned())),
+    }
+  }
+}
+
+impl fmt::Display for HmacAlgorithm {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      HmacAlgorithm::Sha256 => write!(f, "SHA256"),
+      HmacAlgorithm::Sha512 => write!(f, "SHA512"),
+    }
+  }
+}
+
+impl fmt::Display for Signing {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Signing::Hmac { algorithm, header, .. } => write!(f, "HMAC-{} in {}", algorithm, header.bold()),
+      Signing::AwsSigv4 { region, service, .. } => write!(f, "AWS SigV4 for {} in {}", service.bold(), region.bold()),
+    }
+  }
+}
+
+// Redirects followed by default, as many as browsers do.
+pub const REDIRECTS: usize = 10;
+
+// How the client of an upstream speaks HTTP.
+#[derive(Debug)]
+pub struct Http {
+  pub protocol: Option<Protocol>,
+  // Redirects are not followed when 0
+  pub redirects: usize,
+  // Encodings offered to the server, and decoded from the responses
+  pub decompress: Vec<Compression>,
+}
+
+impl Default for Http {
+  fn default() -> Http {
+    Http {
+      protocol: None,
+      redirects: REDIRECTS,
+      decompress: vec![],
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+  Gzip,
+  Brotli,
+  Deflate,
+}
+
+impl fmt::Display for Compression {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Compression::Gzip => write!(f, "gzip"),
+      Compression::Brotli => write!(f, "brotli"),
+      Compression::Deflate => write!(f, "deflate"),
+    }
+  }
+}
+
+// How much of the response bodies is read before a request is complete.
+// Discarded bodies are received without being timed or counted, and bodies
+// read up to a limit close their connection when they are longer.
+#[derive(Debug, Clone, Copy)]
+pub enum ReadBody {
+  Discard,
+  Full,
+  Limit(u64),
+}
+
+// HTTP version forced for the requests of an upstream, which otherwise is
+// negotiated with the server.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Protocol {
+  Http1,
+  // HTTP/2 over cleartext, with prior knowledge
+  H2c,
+  // HTTP/2 over TLS, negotiated with ALPN
+  H2,
+  Http3,
+}
+
+impl fmt::Display for Protocol {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Protocol::Http1 => write!(f, "HTTP/1.1"),
+      Protocol::H2c => write!(f, "HTTP/2 over cleartext"),
+      Protocol::H2 => write!(f, "HTTP/2"),
+      Protocol::Http3 => write!(f, "HTTP/3"),
+    }
+  }
+}
+
+// TLS settings of an upstream, merged with the scenario's.
+#[derive(Debug)]
+pub struct Tls {
+  pub ca: Vec<Certificate>,
+  pub identity: Option<Identity>,
+  pub insecure_skip_verify: bool,
+  pub sni: Option<String>,
+  pub min_version: Option<Version>,
+  pub rustls: bool,
+}
+
+impl fmt::Display for Tls {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let mut settings = vec![];
+
+    if !self.ca.is_empty() {
+      settings.push(format!("CA bundle of {} certificates", self.ca.len()));
+    }
+    if self.identity.is_some() {
+      settings.push("client certificate".to_owned());
+    }
+    if self.insecure_skip_verify {
+      settings.push("insecure".red().to_string());
+    }
+    if let Some(ref sni) = self.sni {
+      settings.push(format!("SNI {}", sni.bold()));
+    }
+    match self.min_version {
+      Some(Version::TLS_1_0) => settings.push("TLS 1.0+".to_owned()),
+      Some(Version::TLS_1_1) => settings.push("TLS 1.1+".to_owned()),
+      Some(Version::TLS_1_2) => settings.push("TLS 1.2+".to_owned()),
+      Some(Version::TLS_1_3) => settings.push("TLS 1.3".to_owned()),
+      _ => {}
+    }
+
+    write!(f, "{}", settings.join(", "))
+  }
+}
+
+// Where the connections of an upstream go and come from. With several source
+// addresses, requests use each of them in turn.
+#[derive(Debug, Default)]
+pub struct Network {
+  pub resolve: Vec<(String, IpAddr)>,
+  pub proxy: Option<Proxy>,
+  pub bind: Vec<IpAddr>,
+  pub next_bind: AtomicUsize,
+}
+
+impl Network {
+  pub fn source(&self) -> Option<IpAddr> {
+    match self.bind.len() {
+      0 => None,
+      len => Some(self.bind[self.next_bind.fetch_add(1, Ordering::Relaxed) % len]),
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.resolve.is_empty() && self.proxy.is_none() && self.bind.is_empty()
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct Proxy {
+  pub url: String,
+  pub username: Option<String>,
+  pub password: Option<String>,
+  pub no_proxy: Vec<String>,
+}
+
+impl fmt::Display for Network {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let mut settings = vec![];
+
+    for (host, addr) in &self.resolve {
+      settings.push(format!("{} at {}", host.bold(), addr));
+    }
+    if let Some(ref proxy) = self.proxy {
+      settings.push(format!("proxy {}", proxy.url.bold()));
+    }
+    if !self.bind.is_empty() {
+      settings.push(format!("from {}", self.bind.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")));
+    }
+
+    write!(f, "{}", settings.join(", "))
+  }
+}
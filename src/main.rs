@@ -8,19 +8,24 @@ extern crate colored;
 extern crate histogram;
 extern crate rand;
 extern crate reqwest;
+extern crate yaml_rust;
 
 mod config;
 mod datasource;
+mod generator;
 mod interface;
 mod scheduler;
+mod template;
 mod util;
 
 use chrono::prelude::*;
 use clap::App;
 use indicatif::{ProgressBar, ProgressStyle};
+use rand::{rngs::StdRng, SeedableRng};
 use std::error::Error;
 use std::fs::File;
 use std::io::prelude::*;
+use std::sync::atomic::Ordering;
 use std::sync::mpsc::{self, Sender};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
@@ -32,6 +37,7 @@ use crate::interface::specs;
 fn main() {
   if let Err(error) = parse_cli() {
     println!("ERROR: {:#}", error);
+    process::exit(1);
   }
 }
 
@@ -45,18 +51,36 @@ fn parse_cli() -> Result<(), Box<dyn Error>> {
   match matches.subcommand() {
     ("run", Some(args)) => run(options, args),
     ("check", Some(args)) => check(&options, args),
+    ("schema", Some(_)) => schema(),
     _ => usage(&mut app),
   }
 }
 
 fn run(options: config::Options, args: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
   let config = config::Config::read(args.value_of("config").unwrap())?;
-  let scenario = Arc::new(config.create_scenario());
-  let timeout = scenario.options.timeout;
+  let mut scenario = config.create_scenario()?;
   let options = Arc::new(options);
   let results = Arc::new(Mutex::new(vec![]));
   let do_log = args.occurrences_of("disable_logging") == 0;
 
+  // Every random choice of the run derives from this seed, so it can be replayed
+  let seed = match args.value_of("seed") {
+    Some(seed) => seed.parse::<u64>().map_err(|_| format!("invalid seed '{}'", seed))?,
+    None => rand::random(),
+  };
+  let mut rng = StdRng::seed_from_u64(seed);
+
+  util::info(&format!("using random seed {}", seed));
+
+  for pool in scenario.datasources.values_mut() {
+    pool.shuffle(&mut rng);
+  }
+
+  let scenario = Arc::new(scenario);
+  let timeout = scenario.drain();
+  let connections = Arc::clone(&scenario.connections);
+  let distribution = Arc::clone(&scenario.distribution);
+
   let (tx, rx) = mpsc::channel::<Result<Success, Failure>>();
 
   // Spawn a receiver thread to compile the requests results
@@ -64,7 +88,8 @@ fn run(options: config::Options, args: &clap::ArgMatches) -> Result<(), Box<dyn
     let results = results.clone();
 
     thread::spawn(move || {
-      let log_name = Utc::now().format("candia-%Y-%m-%dT%H:%M:%S.csv").to_string();
+      // The seed is part of the name so that a run can be replayed from its log
+      let log_name = format!("{}-seed-{}.csv", Utc::now().format("candia-%Y-%m-%dT%H:%M:%S"), seed);
       let mut log_file = if do_log {
         Some(File::create(log_name).expect("could not open log file for writing"))
       } else {
@@ -77,7 +102,7 @@ fn run(options: config::Options, args: &clap::ArgMatches) -> Result<(), Box<dyn
 
       for result in rx {
         if let Some(log_file) = &mut log_file {
-          let log = util::log(&result);
+          let log = util::log(&result, seed);
 
           log_file.write_all(log.as_bytes()).unwrap();
         }
@@ -87,7 +112,7 @@ fn run(options: config::Options, args: &clap::ArgMatches) -> Result<(), Box<dyn
     });
   }
 
-  let duration = (scenario.schedulers.iter().map(|s| s.duration()).sum::<u64>()) + scenario.options.timeout;
+  let duration = (scenario.schedulers.iter().map(|s| s.duration()).sum::<u64>()) + timeout;
   let mut step = 0;
   let pb = Arc::new(ProgressBar::new(duration));
 
@@ -111,13 +136,18 @@ fn run(options: config::Options, args: &clap::ArgMatches) -> Result<(), Box<dyn
       loop {
         pbclone.set_prefix(&format!("Step {}:", step));
         pbclone.set_position((util::current_epoch() - scenario.start) as u64);
-        let result = scheduler::tick(&Arc::clone(&options), &Arc::clone(&scenario), scheduler, start, &Sender::clone(&tx), &pbclone);
+        let result = scheduler::tick(&Arc::clone(&options), &Arc::clone(&scenario), scheduler, start, &Sender::clone(&tx), &pbclone, &mut rng);
 
         // A scheduler can tell us if it is finished or not, if it is, we skip to the next scheduler in line
         if let State::Stop = result {
           break;
         }
 
+        // An exhausted datasource can end the whole scenario
+        if scenario.stopped.load(Ordering::SeqCst) {
+          return;
+        }
+
         // We send a tick every second for the schedule to be able to schedule its requests
         thread::sleep(Duration::from_secs(1));
       }
@@ -136,20 +166,26 @@ fn run(options: config::Options, args: &clap::ArgMatches) -> Result<(), Box<dyn
 
   pb.finish_with_message("done");
 
-  result::process(&results);
+  result::process(&results, &connections, &distribution);
 
   Ok(())
 }
 
 fn check(_options: &config::Options, args: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
   let config = config::Config::read(args.value_of("config").unwrap())?;
-  let scenario = config.create_scenario();
+  let scenario = config.create_scenario()?;
 
   print!("{:#}", scenario);
 
   Ok(())
 }
 
+fn schema() -> Result<(), Box<dyn Error>> {
+  println!("{}", config::Config::schema());
+
+  Ok(())
+}
+
 fn usage(app: &mut App) -> ! {
   let _ = app.print_help();
   process::exit(1)
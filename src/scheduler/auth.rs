@@ -0,0 +1,302 @@
+use rand::Rng;
+use reqwest::blocking::{Client, Request};
+use serde_json::Value;
+use std::time::{Duration, Instant};
+
+use crate::specs::{Auth, AuthKind, Http, Scenario, Token, Upstream};
+
+use super::dispatcher;
+
+// Token to send with a request, fetched again when it is missing or about to
+// expire. The lock is held while fetching, so concurrent requests wait for the
+// new token instead of all fetching their own.
+pub fn token<R: Rng>(scenario: &Scenario, auth: &Auth, req: &Upstream, rng: &mut R) -> Result<String, String> {
+  let mut token = auth.token.lock().unwrap();
+
+  if let Some(ref current) = *token {
+    if current.renew.is_none_or(|renew| Instant::now() < renew) {
+      return Ok(current.value.clone());
+    }
+  }
+
+  let fresh = fetch(scenario, &auth.kind, req, rng)?;
+  let value = fresh.value.clone();
+
+  *token = Some(fresh);
+
+  Ok(value)
+}
+
+// Forget a token rejected by an upstream, unless it was already renewed.
+pub fn invalidate(auth: &Auth, value: &str) {
+  let mut token = auth.token.lock().unwrap();
+
+  if token.as_ref().is_some_and(|token| token.value == value) {
+    *token = None;
+  }
+}
+
+// Token endpoints are requested with the TLS and network settings of the
+// upstream needing the token.
+fn fetch<R: Rng>(scenario: &Scenario, kind: &AuthKind, req: &Upstream, rng: &mut R) -> Result<Token, String> {
+  let start = Instant::now();
+
+  match kind {
+    AuthKind::Bearer(token) => Ok(Token { value: token.clone(), renew: None }),
+
+    AuthKind::OAuth2 {
+      token_url,
+      client_id,
+      client_secret,
+      scope,
+      refresh_before,
+    } => {
+      let mut form = vec![("grant_type", "client_credentials"), ("client_id", client_id), ("client_secret", client_secret)];
+
+      if let Some(scope) = scope {
+        form.push(("scope", scope));
+      }
+
+      let client = dispatcher::uncounted(scenario, req.tls.as_ref(), &Http::default(), &req.network, req.network.source(), token_url)?;
+      let request = client.post(token_url).form(&form).build().map_err(|err| err.to_string())?;
+      let response = send(&client, request)?;
+      let value = lookup(&response, "access_token").ok_or("no 'access_token' in the response")?;
+      let lifetime = lookup(&response, "expires_in").and_then(|lifetime| lifetime.parse().ok());
+
+      Ok(Token {
+        value,
+        renew: lifetime.map(|lifetime| renewal(start, lifetime, *refresh_before)),
+      })
+    }
+
+    AuthKind::Login {
+      upstream,
+      token,
+      expires_in,
+      ttl,
+      refresh_before,
+    } => {
+      let (client, request) = dispatcher::login(scenario, &scenario.upstreams[*upstream], rng)?;
+      let response = send(&client, request)?;
+      let value = lookup(&response, token).ok_or_else(|| format!("no '{}' in the response", token))?;
+      let lifetime = expires_in.as_ref().and_then(|path| lookup(&response, path)).and_then(|lifetime| lifetime.parse().ok()).or(*ttl);
+
+      Ok(Token {
+        value,
+        renew: lifetime.map(|lifetime| renewal(start, lifetime, *refresh_before)),
+      })
+    }
+  }
+}
+
+// Send a token request, expecting a JSON response.
+fn send(client: &Client, request: Request) -> Result<Value, String> {
+  let response = client.execute(request).map_err(|err| dispatcher::describe(&err))?;
+
+  if !response.status().is_success() {
+    return Err(format!("token request failed with status {}", response.status().as_u16()));
+  }
+
+  let body = response.bytes().map_err(|err| err.to_string())?;
+
+  serde_json::from_slice(&body).map_err(|err| format!("invalid token response: {}", err))
+}
+
+// Scalar at a dotted path of a JSON document, such as `data.token` or
+// `tokens.0`, as a string.
+fn lookup(value: &Value, path: &str) -> Option<String> {
+  let value = path.split('.').try_fold(value, |value, key| match value {
+    Value::Array(items) => key.parse::<usize>().ok().and_then(|idx| items.get(idx)),
+    _ => value.get(key),
+  })?;
+
+  match value {
+    Value::String(value) => Some(value.clone()),
+    Value::Number(value) => Some(value.to_string()),
+    _ => None,
+  }
+}
+
+// Tokens are renewed a little before they expire, but never before half their
+// lifetime.
+fn renewal(start: Instant, lifetime: u64, refresh_before: u64) -> Instant {
+  start + Duration::from_secs(lifetime - refresh_before.min(lifetime / 2))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use rand::{rngs::StdRng, SeedableRng};
+  use serde_json::json;
+  use std::io::{BufRead, BufReader, Read, Write};
+  use std::net::TcpListener;
+  use std::sync::{Arc, Mutex};
+  use std::{env, fs, process, thread};
+
+  use crate::config::{self, Config};
+
+  // Token endpoint at `/token` handing out `token-1`, `token-2`... and an API
+  // at `/api` answering with `status`, one request per connection. The lines
+  // of the requests received are recorded.
+  fn serve(status: u16) -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = format!("http://{}", listener.local_addr().unwrap());
+    let received = Arc::new(Mutex::new(vec![]));
+    let log = Arc::clone(&received);
+
+    thread::spawn(move || {
+      let mut tokens = 0;
+
+      for stream in listener.incoming() {
+        let mut reader = BufReader::new(stream.unwrap());
+        let mut line = String::new();
+        let mut length = 0;
+
+        reader.read_line(&mut line).unwrap();
+
+        let request = line.trim().to_owned();
+
+        loop {
+          let mut header = String::new();
+
+          reader.read_line(&mut header).unwrap();
+
+          match header.trim().to_ascii_lowercase().strip_prefix("content-length:") {
+            Some(value) => length = value.trim().parse().unwrap(),
+            None if header.trim().is_empty() => break,
+            None => {}
+          }
+        }
+
+        let mut body = vec![0; length];
+
+        reader.read_exact(&mut body).unwrap();
+        log.lock().unwrap().push(format!("{} {}", request, String::from_utf8_lossy(&body)).trim().to_owned());
+
+        let (code, body) = if request.contains(" /token ") {
+          tokens += 1;
+          (200, json!({"access_token": format!("token-{}", tokens), "expires_in": 3600}).to_string())
+        } else {
+          (status, String::new())
+        };
+
+        let response = format!(
+          "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+          code,
+          body.len(),
+          body
+        );
+
+        reader.get_mut().write_all(response.as_bytes()).unwrap();
+      }
+    });
+
+    (addr, received)
+  }
+
+  // Scenario with a single `api` upstream authenticated with OAuth2.
+  fn scenario(name: &str, addr: &str) -> Scenario {
+    let path = env::temp_dir().join(format!("candia-auth-{}-{}.yml", process::id(), name));
+    let content = format!(
+      "schedulers:\n  - kind: Pause\n    args:\n      duration: 1\nupstreams:\n  - name: api\n    method: GET\n    url: {0}/api\n    auth:\n      kind: oauth2\n      token_url: {0}/token\n      client_id: loadtest\n      client_secret: secret\n",
+      addr
+    );
+
+    fs::write(&path, content).unwrap();
+
+    let scenario = Config::read(path.to_str().unwrap()).unwrap().create_scenario().unwrap();
+
+    fs::remove_file(&path).unwrap();
+    scenario
+  }
+
+  fn auth(scenario: &Scenario) -> &Auth {
+    scenario.upstreams[0].auth.as_ref().unwrap()
+  }
+
+  #[test]
+  fn lookup_reads_scalars_at_dotted_paths() {
+    let value = json!({"data": {"token": "abc", "tokens": [{"value": 42}]}, "list": ["x"]});
+
+    assert_eq!(lookup(&value, "data.token"), Some("abc".to_owned()));
+    assert_eq!(lookup(&value, "data.tokens.0.value"), Some("42".to_owned()));
+    assert_eq!(lookup(&value, "list.0"), Some("x".to_owned()));
+    assert_eq!(lookup(&value, "data"), None);
+    assert_eq!(lookup(&value, "list.1"), None);
+    assert_eq!(lookup(&value, "data.missing"), None);
+  }
+
+  #[test]
+  fn oauth2_token_is_fetched_once() {
+    let (addr, received) = serve(200);
+    let scenario = scenario("once", &addr);
+    let mut rng = StdRng::seed_from_u64(0);
+
+    assert_eq!(token(&scenario, auth(&scenario), &scenario.upstreams[0], &mut rng), Ok("token-1".to_owned()));
+    assert_eq!(token(&scenario, auth(&scenario), &scenario.upstreams[0], &mut rng), Ok("token-1".to_owned()));
+    assert_eq!(
+      *received.lock().unwrap(),
+      vec!["POST /token HTTP/1.1 grant_type=client_credentials&client_id=loadtest&client_secret=secret"]
+    );
+
+    // Renewed 30 seconds before the token expires
+    let renew = auth(&scenario).token.lock().unwrap().as_ref().unwrap().renew.unwrap();
+
+    assert!(renew > Instant::now() + Duration::from_secs(3500));
+    assert!(renew <= Instant::now() + Duration::from_secs(3570));
+  }
+
+  #[test]
+  fn expired_token_is_renewed() {
+    let (addr, _) = serve(200);
+    let scenario = scenario("expired", &addr);
+    let mut rng = StdRng::seed_from_u64(0);
+
+    assert_eq!(token(&scenario, auth(&scenario), &scenario.upstreams[0], &mut rng), Ok("token-1".to_owned()));
+
+    auth(&scenario).token.lock().unwrap().as_mut().unwrap().renew = Some(Instant::now());
+
+    assert_eq!(token(&scenario, auth(&scenario), &scenario.upstreams[0], &mut rng), Ok("token-2".to_owned()));
+    assert_eq!(token(&scenario, auth(&scenario), &scenario.upstreams[0], &mut rng), Ok("token-2".to_owned()));
+  }
+
+  #[test]
+  fn rejected_token_is_renewed() {
+    let (addr, received) = serve(401);
+    let scenario = Arc::new(scenario("rejected", &addr));
+    let options = Arc::new(config::Options { verbose: false, log: false });
+    let mut rng = StdRng::seed_from_u64(0);
+
+    dispatcher::request(&options, &scenario, &scenario.upstreams[0], None, &mut rng);
+
+    assert!(auth(&scenario).token.lock().unwrap().is_none());
+    assert_eq!(token(&scenario, auth(&scenario), &scenario.upstreams[0], &mut rng), Ok("token-2".to_owned()));
+    assert_eq!(received.lock().unwrap().len(), 3);
+  }
+
+  #[test]
+  fn renewed_token_is_not_invalidated() {
+    let auth = Auth::new(AuthKind::Bearer("token".to_owned()));
+
+    *auth.token.lock().unwrap() = Some(Token {
+      value: "token-2".to_owned(),
+      renew: None,
+    });
+
+    invalidate(&auth, "token-1");
+    assert_eq!(auth.token.lock().unwrap().as_ref().map(|token| token.value.clone()), Some("token-2".to_owned()));
+
+    invalidate(&auth, "token-2");
+    assert!(auth.token.lock().unwrap().is_none());
+  }
+
+  #[test]
+  fn renewal_leaves_at_least_half_the_lifetime() {
+    let start = Instant::now();
+
+    assert_eq!(renewal(start, 3600, 30), start + Duration::from_secs(3570));
+    assert_eq!(renewal(start, 40, 30), start + Duration::from_secs(20));
+    assert_eq!(renewal(start, 0, 30), start);
+  }
+}
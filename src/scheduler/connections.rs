@@ -0,0 +1,90 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use http::Extensions;
+use hyper_util::client::legacy::connect::{Connection, HttpInfo};
+use reqwest::blocking::Response;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::specs::ConnectionStats;
+
+// Layer of the connector of a client, counting the connections it opens and
+// recording their addresses, so the requests sent over them can be told apart.
+#[derive(Clone)]
+pub struct Counter {
+  pub stats: Arc<ConnectionStats>,
+}
+
+#[derive(Clone)]
+pub struct Counted<S> {
+  inner: S,
+  stats: Arc<ConnectionStats>,
+}
+
+impl<S> Layer<S> for Counter {
+  type Service = Counted<S>;
+
+  fn layer(&self, inner: S) -> Counted<S> {
+    Counted {
+      inner,
+      stats: Arc::clone(&self.stats),
+    }
+  }
+}
+
+impl<S, R> Service<R> for Counted<S>
+where
+  S: Service<R>,
+  S::Response: Connection,
+  S::Future: Send + 'static,
+{
+  type Response = S::Response;
+  type Error = S::Error;
+  type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+  fn poll_ready(&mut self, cx: &mut Context) -> Poll<Result<(), S::Error>> {
+    self.inner.poll_ready(cx)
+  }
+
+  fn call(&mut self, request: R) -> Self::Future {
+    let stats = Arc::clone(&self.stats);
+    let connection = self.inner.call(request);
+
+    Box::pin(async move {
+      let connection = connection.await;
+
+      match connection {
+        Ok(ref connection) => {
+          let mut extensions = Extensions::new();
+          connection.connected().get_extras(&mut extensions);
+
+          if let Some(info) = extensions.get::<HttpInfo>() {
+            stats.fresh.lock().unwrap().insert((info.local_addr(), info.remote_addr()));
+          }
+
+          stats.opened.fetch_add(1, Ordering::SeqCst);
+        }
+
+        Err(_) => {
+          stats.failed.fetch_add(1, Ordering::SeqCst);
+        }
+      }
+
+      connection
+    })
+  }
+}
+
+// A response received over a connection opened for an earlier request counts
+// as reused. Responses of redirected requests only account for the last one.
+pub fn record(stats: &ConnectionStats, response: &Response) {
+  if let Some(info) = response.extensions().get::<HttpInfo>() {
+    if !stats.fresh.lock().unwrap().remove(&(info.local_addr(), info.remote_addr())) {
+      stats.reused.fetch_add(1, Ordering::SeqCst);
+    }
+  }
+}
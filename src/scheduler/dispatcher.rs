@@ -1,16 +1,38 @@
+use std::error::Error;
+use std::io::{self, Read};
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::sync::atomic::Ordering;
 use std::sync::{mpsc::Sender, Arc};
 use std::thread;
 use std::time::Duration;
 
 use indicatif::ProgressBar;
-use rand::{random, thread_rng, Rng};
-use reqwest::Client;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use reqwest::blocking::multipart::{self, Form};
+use reqwest::blocking::{Client, ClientBuilder, Request, RequestBuilder, Response};
+use reqwest::cookie::CookieStore;
+use reqwest::header::{HeaderValue, HOST};
+use reqwest::redirect::Policy;
+use reqwest::{NoProxy, Proxy, StatusCode, Url, Version};
 
+use crate::datasource::Exhausted;
 use crate::result::{Failure, State, Success};
-use crate::specs::{HttpMethod::*, Upstream};
+use crate::specs::{Body, ClientPool, Compression, Http, HttpMethod::*, Kind, Network, Part, Protocol, ReadBody};
+use crate::template::Bindings;
 use crate::{config, result, scheduler::*, specs, util};
 
-pub fn tick<'a>(options: &'a Arc<config::Options>, scenario: &Arc<specs::Scenario>, scheduler: &Scheduler, start: f64, tx: &Sender<Result<Success, Failure>>, pb: &ProgressBar) -> result::State {
+use super::connections::{self, Counter};
+use super::{auth, grpc, signing, socket, websocket};
+
+pub fn tick(
+  options: &Arc<config::Options>,
+  scenario: &Arc<specs::Scenario>,
+  scheduler: &Scheduler,
+  start: f64,
+  tx: &Sender<Result<Success, Failure>>,
+  pb: &ProgressBar,
+  rng: &mut StdRng,
+) -> result::State {
   // How much time passed since this scheduler was created?
   let elapsed = util::current_epoch() - start;
   let mut threads = vec![];
@@ -26,36 +48,63 @@ pub fn tick<'a>(options: &'a Arc<config::Options>, scenario: &Arc<specs::Scenari
   };
 
   // If requests must be spawned
-  if let Some((count, interval, upstreams)) = threshold {
-    if interval > 0 && (elapsed as u64) % interval == 0 {
+  if let Some((rate, interval, selector)) = threshold {
+    if interval > 0 && (elapsed as u64).is_multiple_of(interval) {
+      let count = batch_count(rate, (elapsed as u64) / interval);
+
       if options.verbose {
         println!();
       }
 
       pb.set_message(&format!("running batch with {} requests over {} seconds...", count, interval));
 
-      let upstreams = Arc::new(upstreams);
-
       // Spawn a thread for each request to be sent
       for _ in 0..count {
-        let scenario = Arc::clone(&scenario);
-        let tx = Sender::clone(&tx);
-        let options = Arc::clone(&options);
-        let upstreams = Arc::clone(&upstreams);
+        // Pick the upstream the request will be sent to
+        let (selector, upstream) = match selector.and_then(|selector| Some((selector, selector.pick(&scenario.upstreams, rng)?))) {
+          Some(picked) => picked,
+          None => continue,
+        };
+
+        scenario.distribution.record(upstream, &selector.shares(&scenario.upstreams));
+
+        // Hand the request to the next virtual user, if cookies are kept
+        let user = scenario.next_user();
+
+        let scenario = Arc::clone(scenario);
+        let tx = Sender::clone(tx);
+        let options = Arc::clone(options);
+
+        // Each request gets its own generator, seeded from the run's, so its
+        // random choices do not depend on the order the threads are run in
+        let mut rng = StdRng::seed_from_u64(rng.gen());
 
         let thread = thread::spawn(move || {
           // Sleep for a random period of the current interval to distribute the requests
-          thread::sleep(Duration::from_millis(random::<u64>() % (interval * 1000)));
+          thread::sleep(Duration::from_millis(rng.gen_range(0..interval * 1000)));
 
-          let upstreams: Vec<&Upstream> = if upstreams.len() > 0 {
-            scenario.upstreams.iter().filter(|u| upstreams.contains(&u.name)).collect()
-          } else {
-            scenario.upstreams.iter().collect()
-          };
+          let req = &scenario.upstreams[upstream];
 
-          // Pick a random request to be sent
-          if let Some(req) = thread_rng().choose(&upstreams) {
-            tx.send(request(&options, &scenario, req)).unwrap();
+          match req.kind {
+            Kind::Http => {
+              if let Some(result) = request(&options, &scenario, req, user, &mut rng) {
+                tx.send(result).unwrap();
+              }
+            }
+
+            Kind::Websocket(ref websocket) => websocket::session(&options, &scenario, req, websocket, user, &mut rng, &tx),
+
+            Kind::Grpc(ref grpc) => {
+              if let Some(result) = grpc::call(&options, &scenario, req, grpc, &mut rng) {
+                tx.send(result).unwrap();
+              }
+            }
+
+            Kind::Socket(ref socket) => {
+              if let Some(result) = socket::send(&options, &scenario, req, socket, &mut rng) {
+                tx.send(result).unwrap();
+              }
+            }
           }
         });
 
@@ -70,25 +119,359 @@ pub fn tick<'a>(options: &'a Arc<config::Options>, scenario: &Arc<specs::Scenari
   State::Stop
 }
 
-pub fn request(options: &Arc<config::Options>, scenario: &Arc<specs::Scenario>, req: &specs::Upstream) -> Result<Success, Failure> {
-  let duration = util::current_epoch_ms();
-  let offset = util::elapsed_since(scenario.start);
+// Fractional rates are spread over consecutive batches, the number of requests
+// for a batch is the increase of the cumulated rate since the previous one.
+fn batch_count(rate: f64, batch: u64) -> u64 {
+  ((rate * (batch + 1) as f64).floor() - (rate * batch as f64).floor()) as u64
+}
+
+// Attributes of an upstream, interpolated for a single request.
+struct Rendered<'a> {
+  url: String,
+  headers: Vec<(String, String)>,
+  body: Option<Payload<'a>>,
+}
+
+enum Payload<'a> {
+  Bytes(Vec<u8>),
+  Form(Vec<(String, String)>),
+  Multipart(Vec<(String, Field<'a>)>),
+}
+
+enum Field<'a> {
+  Text(String),
+  File(&'a specs::FilePart),
+}
+
+// Interpolate the URL, headers and body of an upstream for a request.
+fn render<'a, R: Rng>(scenario: &specs::Scenario, req: &'a specs::Upstream, user: Option<usize>, rng: &mut R) -> Result<Rendered<'a>, String> {
+  let mut bindings = Bindings::default();
+  let url = req.url.render(scenario, &mut bindings, rng)?;
+
+  // Cookies the virtual user would send to that URL can be used in the headers and body
+  if let (Some(user), Ok(url)) = (user, Url::parse(&url)) {
+    bindings.set_cookies(scenario.users[user].jar.cookies(&url).and_then(|cookies| cookies.to_str().ok().map(str::to_owned)));
+  }
+
+  let mut headers = vec![];
+
+  for (key, value) in &req.headers {
+    headers.push((key.to_owned(), value.render(scenario, &mut bindings, rng)?));
+  }
+
+  let body = match req.body {
+    None => None,
+    Some(Body::Text(ref body)) => Some(Payload::Bytes(body.render_bytes(scenario, &mut bindings, rng)?)),
+    Some(Body::Raw(ref body)) => Some(Payload::Bytes(body.clone())),
+
+    Some(Body::Json(ref body)) => {
+      if !headers.iter().any(|(key, _)| key.eq_ignore_ascii_case("content-type")) {
+        headers.push(("Content-Type".to_owned(), "application/json".to_owned()));
+      }
+
+      Some(Payload::Bytes(body.render(scenario, &mut bindings, rng)?.to_string().into_bytes()))
+    }
+
+    Some(Body::Form(ref fields)) => {
+      let mut form = vec![];
+
+      for (key, value) in fields {
+        form.push((key.to_owned(), value.render(scenario, &mut bindings, rng)?));
+      }
+
+      Some(Payload::Form(form))
+    }
+
+    Some(Body::Multipart(ref parts)) => {
+      let mut fields = vec![];
+
+      for (name, part) in parts {
+        let field = match part {
+          Part::Text(value) => Field::Text(value.render(scenario, &mut bindings, rng)?),
+          Part::File(file) => Field::File(file),
+        };
+
+        fields.push((name.to_owned(), field));
+      }
+
+      Some(Payload::Multipart(fields))
+    }
+  };
+
+  Ok(Rendered { url, headers, body })
+}
+
+// Files are read for every request, so they are not kept in memory.
+fn multipart(fields: Vec<(String, Field)>) -> Result<Form, String> {
+  let mut form = Form::new();
+
+  for (name, field) in fields {
+    form = match field {
+      Field::Text(value) => form.text(name, value),
+
+      Field::File(file) => {
+        let mut part = multipart::Part::file(&file.path).map_err(|err| format!("could not read '{}': {}", file.path, err))?;
+
+        if let Some(ref filename) = file.filename {
+          part = part.file_name(filename.to_owned());
+        }
+
+        if let Some(ref content_type) = file.content_type {
+          part = part.mime_str(content_type).map_err(|err| err.to_string())?;
+        }
+
+        form.part(name, part)
+      }
+    };
+  }
+
+  Ok(form)
+}
+
+// Client sending the requests of a virtual user to an upstream, with its
+// cookies and its network, TLS and HTTP settings.
+pub fn client(scenario: &specs::Scenario, tls: Option<&specs::Tls>, http: &Http, network: &Network, source: Option<IpAddr>, user: Option<usize>, url: &str) -> Result<Client, String> {
+  let client = builder(scenario, tls, http, network, source, user, url)?.connector_layer(Counter {
+    stats: Arc::clone(&scenario.connections),
+  });
+
+  client.build().map_err(|err| describe(&err))
+}
+
+// Client of the requests which are not recorded in the results, such as those
+// obtaining tokens, whose connections are not counted either so the number of
+// reused connections only depends on the recorded requests.
+pub fn uncounted(scenario: &specs::Scenario, tls: Option<&specs::Tls>, http: &Http, network: &Network, source: Option<IpAddr>, url: &str) -> Result<Client, String> {
+  builder(scenario, tls, http, network, source, None, url)?.build().map_err(|err| describe(&err))
+}
+
+fn builder(scenario: &specs::Scenario, tls: Option<&specs::Tls>, http: &Http, network: &Network, source: Option<IpAddr>, user: Option<usize>, url: &str) -> Result<ClientBuilder, String> {
+  let mut client = Client::builder().timeout(Duration::from_secs(scenario.options.timeout));
+
+  // Pinned hosts are reached on the port of the URL
+  for (host, addr) in &network.resolve {
+    client = client.resolve(host, SocketAddr::new(*addr, 0));
+  }
+
+  if let Some(ref proxy) = network.proxy {
+    let mut proxy_url = Url::parse(&proxy.url).map_err(|err| format!("invalid proxy URL '{}': {}", proxy.url, err))?;
+
+    // Credentials are given in the URL, so they are used by SOCKS proxies as well
+    if let Some(ref username) = proxy.username {
+      let _ = proxy_url.set_username(username);
+      let _ = proxy_url.set_password(proxy.password.as_deref());
+    }
+
+    let mut target = Proxy::all(proxy_url).map_err(|err| format!("invalid proxy '{}': {}", proxy.url, err))?;
+
+    if !proxy.no_proxy.is_empty() {
+      target = target.no_proxy(NoProxy::from_string(&proxy.no_proxy.join(",")));
+    }
+
+    client = client.proxy(target);
+  }
+
+  if let Some(source) = source {
+    client = client.local_address(source);
+  }
+
+  if let Some(user) = user {
+    client = client.cookie_provider(Arc::clone(&scenario.users[user].jar));
+  }
+
+  if let Some(tls) = tls {
+    if tls.rustls {
+      client = client.use_rustls_tls();
+    }
+
+    for certificate in &tls.ca {
+      client = client.add_root_certificate(certificate.clone());
+    }
+
+    if let Some(ref identity) = tls.identity {
+      client = client.identity(identity.clone());
+    }
+
+    if let Some(version) = tls.min_version {
+      client = client.min_tls_version(version);
+    }
+
+    client = client.danger_accept_invalid_certs(tls.insecure_skip_verify);
+
+    // The server name is sent to the address of the host of the URL, see `override_sni`
+    if let (Some(ref sni), Ok(url)) = (&tls.sni, Url::parse(url)) {
+      let host = url.host_str().unwrap_or_default();
+      let port = url.port_or_known_default().unwrap_or(443);
+      let addrs = match network.resolve.iter().find(|(name, _)| name == host) {
+        Some((_, addr)) => vec![SocketAddr::new(*addr, port)],
+        None => (host, port).to_socket_addrs().map_err(|err| format!("could not resolve '{}': {}", host, err))?.collect(),
+      };
+
+      client = client.resolve_to_addrs(sni, &addrs);
+    }
+  }
+
+  // Without reuse, connections are closed as soon as their request is done
+  let connections = &scenario.options.connections;
+
+  if !connections.reuse {
+    client = client.pool_max_idle_per_host(0);
+  } else if let Some(pool_size) = connections.pool_size {
+    client = client.pool_max_idle_per_host(pool_size);
+  }
+
+  if let Some(idle_timeout) = connections.idle_timeout {
+    client = client.pool_idle_timeout(Duration::from_secs(idle_timeout));
+  }
+
+  client = client
+    .redirect(match http.redirects {
+      0 => Policy::none(),
+      redirects => Policy::limited(redirects),
+    })
+    .gzip(http.decompress.contains(&Compression::Gzip))
+    .brotli(http.decompress.contains(&Compression::Brotli))
+    .deflate(http.decompress.contains(&Compression::Deflate));
+
+  client = match http.protocol {
+    None => client,
+    Some(Protocol::Http1) => client.http1_only(),
+    Some(Protocol::H2c) => client.http2_prior_knowledge(),
+    // Over TLS, HTTP/2 is negotiated with ALPN, the version of the responses is
+    // checked as servers may choose HTTP/1.1
+    Some(Protocol::H2) => client,
+    #[cfg(feature = "http3")]
+    Some(Protocol::Http3) => client.http3_prior_knowledge(),
+    #[cfg(not(feature = "http3"))]
+    Some(Protocol::Http3) => return Err("HTTP/3 requires candia to be built with the http3 feature".to_owned()),
+  };
+
+  Ok(client)
+}
+
+// Client of an upstream for a virtual user and a source address, kept between
+// requests so their connections can be reused, and replaced after
+// `max_requests` requests.
+fn pooled(scenario: &specs::Scenario, req: &specs::Upstream, user: Option<usize>, url: &str) -> Result<Client, String> {
+  let source = req.network.source();
+  let mut pools = req.pools.lock().unwrap();
+
+  if let Some(pool) = pools.get_mut(&(user, source)) {
+    if scenario.options.connections.max_requests.is_none_or(|max_requests| pool.requests < max_requests) {
+      pool.requests += 1;
+      return Ok(pool.client.clone());
+    }
+  }
+
+  let client = client(scenario, req.tls.as_ref(), &req.http, &req.network, source, user, url)?;
+  pools.insert((user, source), ClientPool { client: client.clone(), requests: 1 });
+
+  Ok(client)
+}
+
+// With a custom server name, the request is sent to that name, which resolves
+// to the host of the URL, while keeping its Host header.
+pub fn override_sni(tls: Option<&specs::Tls>, request: &mut Request) {
+  let sni = match tls.and_then(|tls| tls.sni.as_ref()) {
+    Some(sni) if request.url().scheme() == "https" => sni,
+    _ => return,
+  };
+
+  let host = match request.url().port() {
+    Some(port) => format!("{}:{}", request.url().host_str().unwrap_or_default(), port),
+    None => request.url().host_str().unwrap_or_default().to_owned(),
+  };
+
+  if let Ok(host) = HeaderValue::from_str(&host) {
+    request.headers_mut().entry(HOST).or_insert(host);
+  }
+
+  let _ = request.url_mut().set_host(Some(sni));
+}
 
-  let client = Client::builder().timeout(Duration::from_secs(scenario.options.timeout)).build().unwrap();
-  let url = util::interpolate(&req.url, &scenario.datasources);
+// Reason of a failed request, with the cause of redirect and TLS failures,
+// which are otherwise reported as generic errors.
+pub fn describe(err: &reqwest::Error) -> String {
+  let mut causes = vec![];
+  let mut source = err.source();
+
+  while let Some(cause) = source {
+    causes.push(cause.to_string());
+    source = cause.source();
+  }
+
+  if err.is_redirect() {
+    return format!("could not follow redirects: {}", causes.first().map_or("invalid redirect", String::as_str));
+  }
+
+  // The outermost cause of TLS errors is the most detailed one
+  let tls = causes.iter().find(|cause| {
+    let cause = cause.to_lowercase();
+    ["certificate", "handshake", "alert", "tls", "ssl"].iter().any(|keyword| cause.contains(keyword))
+  });
+
+  match tls {
+    Some(cause) => format!("TLS handshake failed: {}", cause),
+    None => err.to_string(),
+  }
+}
+
+// HTTP version a response was received with.
+fn version(version: Version) -> &'static str {
+  match version {
+    Version::HTTP_09 => "HTTP/0.9",
+    Version::HTTP_10 => "HTTP/1.0",
+    Version::HTTP_11 => "HTTP/1.1",
+    Version::HTTP_2 => "HTTP/2",
+    Version::HTTP_3 => "HTTP/3",
+    _ => "unknown",
+  }
+}
+
+// Read as much of the body of a response as required, returning the number of
+// bytes received, even when the body could not be read until the end.
+// Discarded bodies are still received, within the timeout of the request, so
+// the connection can be reused, but they are not counted and cannot fail it.
+fn read_body(read_body: ReadBody, response: &mut Response) -> Result<u64, (u64, String)> {
+  let limit = match read_body {
+    ReadBody::Discard => {
+      let _ = io::copy(response, &mut io::sink());
+      return Ok(0);
+    }
+    ReadBody::Full => u64::MAX,
+    ReadBody::Limit(limit) => limit,
+  };
+
+  let mut buffer = [0; 16 * 1024];
+  let mut bytes = 0;
+
+  while bytes < limit {
+    let size = (limit - bytes).min(buffer.len() as u64) as usize;
+
+    match response.read(&mut buffer[..size]) {
+      Ok(0) => break,
+      Ok(read) => bytes += read as u64,
+      Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+      Err(err) => return Err((bytes, err.to_string())),
+    }
+  }
+
+  Ok(bytes)
+}
+
+// Request to an upstream, from its rendered attributes.
+fn build(client: &Client, req: &specs::Upstream, rendered: Rendered) -> Result<RequestBuilder, String> {
+  let Rendered { url, headers, body } = rendered;
 
   // TODO: add more methods
   let request = match req.method {
     Get => client.get(&url),
     Post => client.post(&url),
-    _ => return Failure::global(offset, String::new(), 0, String::new()),
+    _ => return Err("unsupported HTTP method".to_owned()),
   };
 
   // Add headers
-  let request = req
-    .headers
-    .iter()
-    .fold(request, |r, (key, value)| r.header::<&str, &str>(&key, &util::interpolate(value, &scenario.datasources)));
+  let request = headers.iter().fold(request, |r, (key, value)| r.header::<&str, &str>(key, value));
 
   // Add Basic authentication
   let request = match req.basic {
@@ -96,25 +479,124 @@ pub fn request(options: &Arc<config::Options>, scenario: &Arc<specs::Scenario>,
     Some(ref basic) => request.basic_auth(&basic.username, Some(&basic.password)),
   };
 
-  let request = match &req.body {
+  let request = match body {
     None => request,
-    Some(body) => request.body(util::interpolate(&body, &scenario.datasources)),
+    Some(Payload::Bytes(body)) => request.body(body),
+    Some(Payload::Form(fields)) => request.form(&fields),
+    Some(Payload::Multipart(fields)) => request.multipart(multipart(fields)?),
+  };
+
+  Ok(request)
+}
+
+// Request to the upstream supplying the token of a login authentication, which
+// is sent without an authentication of its own.
+pub fn login<R: Rng>(scenario: &specs::Scenario, req: &specs::Upstream, rng: &mut R) -> Result<(Client, Request), String> {
+  let rendered = render(scenario, req, None, rng).map_err(|label| format!("datasource '{}' is exhausted", label))?;
+  let client = uncounted(scenario, req.tls.as_ref(), &req.http, &req.network, req.network.source(), &rendered.url)?;
+  let mut request = build(&client, req, rendered)?.build().map_err(|err| err.to_string())?;
+
+  override_sni(req.tls.as_ref(), &mut request);
+
+  Ok((client, request))
+}
+
+// A datasource in the once mode ran out of values, the request is not sent and
+// the scenario is stopped if required.
+pub fn exhausted(scenario: &specs::Scenario, req: &specs::Upstream, offset: u64, label: String) -> Option<Result<Success, Failure>> {
+  if scenario.datasources[&label].exhausted == Exhausted::Stop {
+    scenario.stopped.store(true, Ordering::SeqCst);
+    return None;
+  }
+
+  Some(Failure::global(offset, req.url.to_string(), 0, format!("datasource '{}' is exhausted", label)))
+}
+
+pub fn request<R: Rng>(options: &Arc<config::Options>, scenario: &Arc<specs::Scenario>, req: &specs::Upstream, user: Option<usize>, rng: &mut R) -> Option<Result<Success, Failure>> {
+  // Tokens are obtained before the request is timed
+  let token = match req.auth {
+    None => None,
+    Some(ref auth) => match auth::token(scenario, auth, req, rng) {
+      Ok(token) => Some(token),
+      Err(reason) => {
+        let offset = util::elapsed_since(scenario.start);
+
+        return Some(Failure::global(offset, req.url.to_string(), 0, format!("could not authenticate: {}", reason)));
+      }
+    },
   };
 
-  let request = request.build().unwrap();
+  let duration = util::current_epoch_ms();
+  let offset = util::elapsed_since(scenario.start);
+
+  let rendered = match render(scenario, req, user, rng) {
+    Ok(rendered) => rendered,
+
+    Err(label) => return exhausted(scenario, req, offset, label),
+  };
+
+  let url = rendered.url.clone();
+
+  let client = match pooled(scenario, req, user, &url) {
+    Ok(client) => client,
+    Err(reason) => return Some(Failure::global(offset, url, 0, reason)),
+  };
+
+  let request = match build(&client, req, rendered) {
+    Ok(request) => request,
+    Err(reason) => return Some(Failure::global(offset, url, 0, reason)),
+  };
+
+  let request = match token {
+    None => request,
+    Some(ref token) => request.bearer_auth(token),
+  };
+
+  let mut request = match request.build() {
+    Ok(request) => request,
+    Err(err) => return Some(Failure::global(offset, url, 0, err.to_string())),
+  };
   let request_desc = format!("{} {}", request.method(), request.url());
 
-  match client.execute(request) {
-    Ok(response) => {
+  // Signatures cover the request as it will be sent
+  if let Some(ref signing) = req.signing {
+    if let Err(reason) = signing::sign(signing, &mut request) {
+      return Some(Failure::global(offset, request_desc, 0, format!("could not sign the request: {}", reason)));
+    }
+  }
+
+  override_sni(req.tls.as_ref(), &mut request);
+
+  let result = match client.execute(request) {
+    Ok(mut response) => {
       if options.verbose {
         util::write_flush("·");
       }
 
-      let duration = util::current_epoch_ms() - duration;
+      connections::record(&scenario.connections, &response);
+
+      // A rejected token is renewed for the following requests
+      if let (StatusCode::UNAUTHORIZED, Some(auth), Some(token)) = (response.status(), &req.auth, &token) {
+        auth::invalidate(auth, token);
+      }
+
+      let code = response.status().as_u16();
+      let protocol = version(response.version());
+      let mismatch = req.http.protocol == Some(Protocol::H2) && response.version() != Version::HTTP_2;
 
-      match response.status().as_u16() {
-        200..=399 => Success::new(offset, request_desc, duration, response.status().as_u16()),
-        code => Failure::http(offset, request_desc, duration, code, String::new()),
+      // When the body is read, the request lasts until it is received
+      let headers = util::current_epoch_ms() - duration;
+      let received = read_body(req.read_body, &mut response);
+      let duration = match req.read_body {
+        ReadBody::Discard => headers,
+        _ => util::current_epoch_ms() - duration,
+      };
+
+      match (code, received) {
+        (_, Err((bytes, reason))) => Failure::http(offset, request_desc, duration, code, protocol, bytes, format!("could not read the body: {}", reason)),
+        (_, Ok(bytes)) if mismatch => Failure::http(offset, request_desc, duration, code, protocol, bytes, format!("protocol mismatch: {} instead of HTTP/2", protocol)),
+        (200..=399, Ok(bytes)) => Success::new(offset, request_desc, duration, code, protocol, bytes),
+        (code, Ok(bytes)) => Failure::http(offset, request_desc, duration, code, protocol, bytes, String::new()),
       }
     }
 
@@ -125,7 +607,140 @@ pub fn request(options: &Arc<config::Options>, scenario: &Arc<specs::Scenario>,
 
       let duration = util::current_epoch_ms() - duration;
 
-      Failure::global(offset, request_desc, duration, err.to_string())
+      Failure::global(offset, request_desc, duration, describe(&err))
     }
+  };
+
+  Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::io::{BufRead, BufReader, Write};
+  use std::net::TcpListener;
+  use std::sync::atomic::AtomicUsize;
+
+  use crate::config::Config;
+
+  // HTTP/1.1 server keeping its connections alive, answering every request
+  // with a body of `size` bytes. Returns its address and the number of
+  // connections it accepted.
+  fn serve(size: usize) -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = format!("http://{}", listener.local_addr().unwrap());
+    let accepted = Arc::new(AtomicUsize::new(0));
+    let count = Arc::clone(&accepted);
+
+    thread::spawn(move || {
+      for stream in listener.incoming() {
+        count.fetch_add(1, Ordering::SeqCst);
+
+        thread::spawn(move || {
+          let mut reader = BufReader::new(stream.unwrap());
+          let body = vec![b'x'; size];
+
+          loop {
+            let mut line = String::new();
+
+            // The request ends with an empty line, GET requests having no body
+            loop {
+              line.clear();
+
+              match reader.read_line(&mut line) {
+                Ok(0) | Err(_) => return,
+                Ok(_) if line.trim().is_empty() => break,
+                Ok(_) => {}
+              }
+            }
+
+            let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", size);
+            let stream = reader.get_mut();
+
+            if stream.write_all(head.as_bytes()).and_then(|_| stream.write_all(&body)).is_err() {
+              return;
+            }
+          }
+        });
+      }
+    });
+
+    (addr, accepted)
+  }
+
+  fn scenario(addr: &str, read_body: &str) -> Arc<specs::Scenario> {
+    Arc::new(Config::scenario(&format!(
+      "schedulers: []\nupstreams:\n  - name: api\n    method: GET\n    url: {}/\n    read_body: {}\n",
+      addr, read_body
+    )))
+  }
+
+  // Sends requests one after the other, returning the number of bytes read
+  // for each.
+  fn send(scenario: &Arc<specs::Scenario>, count: usize) -> Vec<u64> {
+    let options = Arc::new(config::Options { verbose: false, log: false });
+    let mut rng = StdRng::seed_from_u64(0);
+
+    (0..count)
+      .map(|_| match request(&options, scenario, &scenario.upstreams[0], None, &mut rng) {
+        Some(Ok(success)) => success.bytes,
+        Some(Err(failure)) => panic!("request failed: {}", failure.reason),
+        None => panic!("request not sent"),
+      })
+      .collect()
+  }
+
+  #[test]
+  fn discarded_bodies_keep_their_connection() {
+    let (addr, accepted) = serve(100_000);
+    let scenario = scenario(&addr, "discard");
+
+    assert_eq!(send(&scenario, 5), vec![0; 5]);
+    assert_eq!(accepted.load(Ordering::SeqCst), 1);
+    assert_eq!(scenario.connections.opened.load(Ordering::SeqCst), 1);
+    assert_eq!(scenario.connections.reused.load(Ordering::SeqCst), 4);
+  }
+
+  #[test]
+  fn full_bodies_are_counted() {
+    let (addr, accepted) = serve(100_000);
+    let scenario = scenario(&addr, "full");
+
+    assert_eq!(send(&scenario, 3), vec![100_000; 3]);
+    assert_eq!(accepted.load(Ordering::SeqCst), 1);
+  }
+
+  #[test]
+  fn bodies_are_read_up_to_the_limit() {
+    let (addr, accepted) = serve(100_000);
+    let scenario = scenario(&addr, "1000");
+
+    // The rest of the body is not received, so the connection is closed
+    assert_eq!(send(&scenario, 3), vec![1000; 3]);
+    assert_eq!(accepted.load(Ordering::SeqCst), 3);
+  }
+
+  #[test]
+  fn bodies_shorter_than_the_limit_are_read_fully() {
+    let (addr, accepted) = serve(100);
+    let scenario = scenario(&addr, "1000");
+
+    assert_eq!(send(&scenario, 3), vec![100; 3]);
+    assert_eq!(accepted.load(Ordering::SeqCst), 1);
+  }
+
+  #[test]
+  fn connections_are_not_reused_when_disabled() {
+    let (addr, accepted) = serve(10);
+    let scenario = Arc::new(Config::scenario(&format!(
+      "options:\n  connections:\n    reuse: false\nschedulers: []\nupstreams:\n  - name: api\n    method: GET\n    url: {}/\n",
+      addr
+    )));
+
+    assert_eq!(send(&scenario, 3), vec![0; 3]);
+    assert_eq!(accepted.load(Ordering::SeqCst), 3);
+    assert_eq!(scenario.connections.opened.load(Ordering::SeqCst), 3);
+    assert_eq!(scenario.connections.reused.load(Ordering::SeqCst), 0);
   }
 }
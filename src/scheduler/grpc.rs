@@ -0,0 +1,353 @@
+use hyper_util::client::legacy::connect::HttpConnector;
+use prost::Message as _;
+use prost_reflect::prost_types::FileDescriptorProto;
+use prost_reflect::{DescriptorPool, DynamicMessage, MessageDescriptor, MethodDescriptor};
+use rand::Rng;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::{Builder, Runtime};
+use tonic::client::Grpc as Client;
+use tonic::codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder};
+use tonic::metadata::{MetadataKey, MetadataValue};
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Code, Request, Status};
+use tonic_reflection::pb::v1::server_reflection_client::ServerReflectionClient;
+use tonic_reflection::pb::v1::server_reflection_request::MessageRequest;
+use tonic_reflection::pb::v1::server_reflection_response::MessageResponse;
+use tonic_reflection::pb::v1::ServerReflectionRequest;
+use tower_layer::Layer;
+
+use crate::result::{Failure, Success};
+use crate::specs::{Body, ChannelPool, Grpc, Scenario, Upstream};
+use crate::template::Bindings;
+use crate::{config, util};
+
+use super::connections::Counter;
+use super::dispatcher;
+
+// Send a unary call to a gRPC upstream. Calls answered with another status than
+// OK fail with the code of that status, calls which did not reach the server
+// fail without a code.
+pub fn call<R: Rng>(options: &Arc<config::Options>, scenario: &Scenario, req: &Upstream, grpc: &Grpc, rng: &mut R) -> Option<Result<Success, Failure>> {
+  let offset = util::elapsed_since(scenario.start);
+
+  let Rendered { url, metadata, message } = match render(scenario, req, rng) {
+    Ok(rendered) => rendered,
+    Err(label) => return dispatcher::exhausted(scenario, req, offset, label),
+  };
+
+  let request_desc = format!("GRPC {}{}", url.trim_end_matches('/'), grpc.path);
+  let runtime = grpc.runtime.get_or_init(runtime);
+
+  let channel = match channel(scenario, req, grpc, runtime, &url) {
+    Ok(channel) => channel,
+    Err(reason) => return Some(Failure::global(offset, request_desc, 0, reason)),
+  };
+
+  // Methods are described by the server before the call is timed
+  let method = match descriptor(grpc, runtime, &channel) {
+    Ok(method) => method,
+    Err(reason) => return Some(Failure::global(offset, request_desc, 0, format!("could not describe the method: {}", reason))),
+  };
+
+  let request = match request(&method, metadata, message) {
+    Ok(request) => request,
+    Err(reason) => return Some(Failure::global(offset, request_desc, 0, reason)),
+  };
+
+  let path = grpc.path.clone();
+  let codec = DynamicCodec(method.output());
+  let start = util::current_epoch_ms();
+
+  let response = runtime.block_on(async move {
+    let mut client = Client::new(channel);
+
+    client.ready().await.map_err(|err| Status::unavailable(err.to_string()))?;
+    client.unary(request, path, codec).await
+  });
+
+  let duration = util::current_epoch_ms() - start;
+
+  if options.verbose {
+    util::write_flush(if response.is_ok() { "·" } else { "!" });
+  }
+
+  let result = match response {
+    Ok(response) => Success::new(offset, request_desc, duration, 0, "gRPC", response.get_ref().encoded_len() as u64),
+
+    // Statuses raised by the client keep the error they come from, those sent
+    // by the server have none
+    Err(status) if status.source().is_some() => Failure::global(offset, request_desc, duration, cause(&status)),
+
+    Err(status) => Failure::http(offset, request_desc, duration, status.code() as u16, "gRPC", 0, describe(&status)),
+  };
+
+  Some(result)
+}
+
+// Attributes of a gRPC upstream, interpolated for a call. Headers are sent as
+// metadata.
+struct Rendered {
+  url: String,
+  metadata: Vec<(String, String)>,
+  message: Value,
+}
+
+fn render<R: Rng>(scenario: &Scenario, req: &Upstream, rng: &mut R) -> Result<Rendered, String> {
+  let mut bindings = Bindings::default();
+  let url = req.url.render(scenario, &mut bindings, rng)?;
+  let mut metadata = vec![];
+
+  for (key, value) in &req.headers {
+    metadata.push((key.to_lowercase(), value.render(scenario, &mut bindings, rng)?));
+  }
+
+  let message = match req.body {
+    Some(Body::Json(ref body)) => body.render(scenario, &mut bindings, rng)?,
+    _ => Value::Object(Default::default()),
+  };
+
+  Ok(Rendered { url, metadata, message })
+}
+
+fn request(method: &MethodDescriptor, metadata: Vec<(String, String)>, message: Value) -> Result<Request<DynamicMessage>, String> {
+  let message = DynamicMessage::deserialize(method.input(), message).map_err(|err| format!("invalid message for {}: {}", method.input().full_name(), err))?;
+  let mut request = Request::new(message);
+
+  for (key, value) in metadata {
+    let name = MetadataKey::from_bytes(key.as_bytes()).map_err(|err| format!("invalid metadata '{}': {}", key, err))?;
+    let value = value.parse::<MetadataValue<_>>().map_err(|err| format!("invalid metadata '{}': {}", key, err))?;
+
+    request.metadata_mut().insert(name, value);
+  }
+
+  Ok(request)
+}
+
+fn runtime() -> Runtime {
+  Builder::new_multi_thread().enable_all().build().expect("could not start the gRPC runtime")
+}
+
+// Channel to the server of an upstream from a source address, kept between
+// calls unless connections are not reused, and replaced after `max_requests`
+// calls. Channels connect when first used.
+fn channel(scenario: &Scenario, req: &Upstream, grpc: &Grpc, runtime: &Runtime, url: &str) -> Result<Channel, String> {
+  let source = req.network.source();
+  let connections = &scenario.options.connections;
+  let mut channels = grpc.channels.lock().unwrap();
+
+  if let Some(pool) = channels.get_mut(&(url.to_owned(), source)) {
+    if connections.reuse && connections.max_requests.is_none_or(|max_requests| pool.requests < max_requests) {
+      pool.requests += 1;
+      return Ok(pool.channel.clone());
+    }
+  }
+
+  let timeout = Duration::from_secs(scenario.options.timeout);
+  let mut endpoint = Endpoint::from_shared(url.to_owned())
+    .map_err(|err| format!("invalid URL '{}': {}", url, err))?
+    .timeout(timeout)
+    .connect_timeout(timeout);
+
+  if url.starts_with("https://") {
+    let tls = grpc.tls.clone().unwrap_or_default().with_enabled_roots();
+
+    endpoint = endpoint.tls_config(tls).map_err(|err| format!("invalid TLS settings: {}", err))?;
+  }
+
+  let mut connector = HttpConnector::new();
+  connector.enforce_http(false);
+  connector.set_nodelay(true);
+  connector.set_local_address(source);
+
+  let connector = Counter {
+    stats: Arc::clone(&scenario.connections),
+  }
+  .layer(connector);
+
+  // The channel starts its background task on the runtime
+  let _guard = runtime.enter();
+  let channel = endpoint.connect_with_connector_lazy(connector);
+
+  channels.insert(
+    (url.to_owned(), source),
+    ChannelPool {
+      channel: channel.clone(),
+      requests: 1,
+    },
+  );
+
+  Ok(channel)
+}
+
+// Method described by a proto file, read when the scenario is created. Imports
+// are looked for next to the file, then in `includes`.
+pub fn load(proto: &str, includes: &[String], service: &str, method: &str) -> Result<MethodDescriptor, String> {
+  let parent = Path::new(proto).parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."));
+  let includes = std::iter::once(parent.to_owned()).chain(includes.iter().map(PathBuf::from));
+
+  fs::metadata(proto).map_err(|err| format!("could not read '{}': {}", proto, err))?;
+
+  let mut compiler = protox::Compiler::new(includes).map_err(|err| err.to_string())?;
+  compiler.open_file(proto).map_err(|err| err.to_string())?;
+
+  Grpc::find(&compiler.descriptor_pool(), service, method)
+}
+
+// Method described by the proto file of the upstream, or else by the server
+// through reflection. The lock is held while asking the server, so concurrent
+// calls wait for the description instead of all asking for their own.
+fn descriptor(grpc: &Grpc, runtime: &Runtime, channel: &Channel) -> Result<MethodDescriptor, String> {
+  let mut descriptor = grpc.descriptor.lock().unwrap();
+
+  if let Some(ref method) = *descriptor {
+    return Ok(method.clone());
+  }
+
+  let pool = runtime.block_on(reflect(channel.clone(), &grpc.service))?;
+  let method = Grpc::find(&pool, &grpc.service, &grpc.method)?;
+
+  *descriptor = Some(method.clone());
+
+  Ok(method)
+}
+
+// Files describing a service, and the files they import, as served through
+// reflection. Servers usually send the imports along with a file, the missing
+// ones are requested by name.
+async fn reflect(channel: Channel, service: &str) -> Result<DescriptorPool, String> {
+  let mut client = ServerReflectionClient::new(channel);
+  let mut files = HashMap::new();
+  let mut requested = HashSet::new();
+  let mut requests = vec![MessageRequest::FileContainingSymbol(service.to_owned())];
+
+  while let Some(request) = requests.pop() {
+    let request = ServerReflectionRequest {
+      host: String::new(),
+      message_request: Some(request),
+    };
+
+    let mut responses = client
+      .server_reflection_info(tokio_stream::iter(vec![request]))
+      .await
+      .map_err(|status| format!("reflection failed: {}", describe(&status)))?
+      .into_inner();
+
+    let response = responses.message().await.map_err(|status| format!("reflection failed: {}", describe(&status)))?;
+
+    let protos = match response.and_then(|response| response.message_response) {
+      Some(MessageResponse::FileDescriptorResponse(response)) => response.file_descriptor_proto,
+      Some(MessageResponse::ErrorResponse(err)) => return Err(err.error_message),
+      _ => return Err("unexpected reflection response".to_owned()),
+    };
+
+    for proto in protos {
+      let file = FileDescriptorProto::decode(proto.as_slice()).map_err(|err| format!("invalid file descriptor: {}", err))?;
+      files.insert(file.name().to_owned(), file);
+    }
+
+    for file in files.values() {
+      for dependency in &file.dependency {
+        if !files.contains_key(dependency) && requested.insert(dependency.to_owned()) {
+          requests.push(MessageRequest::FileByFilename(dependency.to_owned()));
+        }
+      }
+    }
+  }
+
+  let mut pool = DescriptorPool::new();
+  pool.add_file_descriptor_protos(files.into_values()).map_err(|err| err.to_string())?;
+
+  Ok(pool)
+}
+
+// Status sent by a server, with the name of its code.
+fn describe(status: &Status) -> String {
+  match status.message() {
+    "" => name(status.code()).to_owned(),
+    message => format!("{}: {}", name(status.code()), message),
+  }
+}
+
+// Status raised by the client, with the innermost cause of the error it comes
+// from, such as the reason a connection was refused.
+fn cause(status: &Status) -> String {
+  let mut source = status.source();
+  let mut cause = None;
+
+  while let Some(err) = source {
+    cause = Some(err.to_string());
+    source = err.source();
+  }
+
+  match cause {
+    Some(cause) if cause != status.message() => format!("{}: {}", status.message(), cause),
+    _ => status.message().to_owned(),
+  }
+}
+
+// Name of a status code, as given by the gRPC specification.
+fn name(code: Code) -> &'static str {
+  match code {
+    Code::Ok => "OK",
+    Code::Cancelled => "CANCELLED",
+    Code::Unknown => "UNKNOWN",
+    Code::InvalidArgument => "INVALID_ARGUMENT",
+    Code::DeadlineExceeded => "DEADLINE_EXCEEDED",
+    Code::NotFound => "NOT_FOUND",
+    Code::AlreadyExists => "ALREADY_EXISTS",
+    Code::PermissionDenied => "PERMISSION_DENIED",
+    Code::ResourceExhausted => "RESOURCE_EXHAUSTED",
+    Code::FailedPrecondition => "FAILED_PRECONDITION",
+    Code::Aborted => "ABORTED",
+    Code::OutOfRange => "OUT_OF_RANGE",
+    Code::Unimplemented => "UNIMPLEMENTED",
+    Code::Internal => "INTERNAL",
+    Code::Unavailable => "UNAVAILABLE",
+    Code::DataLoss => "DATA_LOSS",
+    Code::Unauthenticated => "UNAUTHENTICATED",
+  }
+}
+
+// Codec of messages described at runtime, encoding the requests and decoding
+// the responses of a method.
+#[derive(Clone)]
+struct DynamicCodec(MessageDescriptor);
+
+impl Codec for DynamicCodec {
+  type Encode = DynamicMessage;
+  type Decode = DynamicMessage;
+  type Encoder = DynamicCodec;
+  type Decoder = DynamicCodec;
+
+  fn encoder(&mut self) -> DynamicCodec {
+    self.clone()
+  }
+
+  fn decoder(&mut self) -> DynamicCodec {
+    self.clone()
+  }
+}
+
+impl Encoder for DynamicCodec {
+  type Item = DynamicMessage;
+  type Error = Status;
+
+  fn encode(&mut self, item: DynamicMessage, dst: &mut EncodeBuf) -> Result<(), Status> {
+    item.encode(dst).map_err(|err| Status::internal(err.to_string()))
+  }
+}
+
+impl Decoder for DynamicCodec {
+  type Item = DynamicMessage;
+  type Error = Status;
+
+  fn decode(&mut self, src: &mut DecodeBuf) -> Result<Option<DynamicMessage>, Status> {
+    DynamicMessage::decode(self.0.clone(), src).map(Some).map_err(|err| Status::internal(err.to_string()))
+  }
+}
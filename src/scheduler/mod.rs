@@ -1,12 +1,21 @@
+mod auth;
+mod connections;
 mod dispatcher;
+pub mod grpc;
+mod selector;
+mod signing;
+mod socket;
 pub mod strategies;
+pub mod tls;
+mod websocket;
 
 use std::fmt;
 
 pub use self::dispatcher::*;
+pub use self::selector::*;
 
 pub trait Schedulable {
-  fn schedule(&self, start: f64) -> Option<(u64, u64, Vec<String>)>;
+  fn schedule(&self, start: f64) -> Option<(f64, u64, Option<&Selector>)>;
 }
 
 #[derive(Debug)]
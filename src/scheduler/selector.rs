@@ -0,0 +1,164 @@
+use colored::*;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+
+use crate::specs::Upstream;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Selection {
+  #[default]
+  Random,
+  RoundRobin,
+}
+
+// Chooses which upstream each request of a scheduler is sent to, among the
+// upstreams allowed by its filter, according to their weights.
+#[derive(Debug, Default)]
+pub struct Selector {
+  pub upstreams: Vec<String>,
+  pub weights: HashMap<String, f64>,
+  pub selection: Selection,
+  // Current weights of the smooth weighted round-robin, one per upstream
+  current: Mutex<Vec<f64>>,
+}
+
+impl Selector {
+  pub fn new(upstreams: Vec<String>, weights: HashMap<String, f64>, selection: Selection) -> Selector {
+    Selector {
+      upstreams,
+      weights,
+      selection,
+      current: Mutex::new(vec![]),
+    }
+  }
+
+  // Weight of every upstream of the scenario, zero for filtered out upstreams.
+  fn weights(&self, upstreams: &[Upstream]) -> Vec<f64> {
+    upstreams
+      .iter()
+      .map(|upstream| {
+        if !self.upstreams.is_empty() && !self.upstreams.contains(&upstream.name) {
+          0.0
+        } else {
+          *self.weights.get(&upstream.name).unwrap_or(&upstream.weight)
+        }
+      })
+      .collect()
+  }
+
+  // Share of the requests each upstream of the scenario is meant to receive.
+  pub fn shares(&self, upstreams: &[Upstream]) -> Vec<f64> {
+    let weights = self.weights(upstreams);
+    let total: f64 = weights.iter().sum();
+
+    weights.iter().map(|weight| if total > 0.0 { weight / total } else { 0.0 }).collect()
+  }
+
+  // Returns the index of the selected upstream in the scenario's upstreams.
+  pub fn pick<R: Rng>(&self, upstreams: &[Upstream], rng: &mut R) -> Option<usize> {
+    self.choose(&self.weights(upstreams), rng)
+  }
+
+  fn choose<R: Rng>(&self, weights: &[f64], rng: &mut R) -> Option<usize> {
+    match self.selection {
+      Selection::Random => WeightedIndex::new(weights).ok().map(|index| index.sample(rng)),
+
+      Selection::RoundRobin => {
+        let total: f64 = weights.iter().sum();
+
+        if total <= 0.0 {
+          return None;
+        }
+
+        let mut current = self.current.lock().unwrap();
+        current.resize(weights.len(), 0.0);
+
+        for (value, weight) in current.iter_mut().zip(weights) {
+          *value += weight;
+        }
+
+        let (idx, _) = current
+          .iter()
+          .enumerate()
+          .filter(|(idx, _)| weights[*idx] > 0.0)
+          .fold((0, f64::MIN), |max, (idx, value)| if *value > max.1 { (idx, *value) } else { max });
+
+        current[idx] -= total;
+
+        Some(idx)
+      }
+    }
+  }
+}
+
+impl fmt::Display for Selector {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    if !self.upstreams.is_empty() {
+      writeln!(f, "    {} {}", "upstreams:".dimmed(), self.upstreams.join(", "))?;
+    }
+
+    if !self.weights.is_empty() {
+      let weights = self.weights.iter().map(|(name, weight)| format!("{}={}", name, weight)).collect::<Vec<String>>();
+
+      writeln!(f, "    {} {}", "weights:".dimmed(), weights.join(", "))?;
+    }
+
+    if self.selection == Selection::RoundRobin {
+      writeln!(f, "    {} round-robin", "selection:".dimmed())?;
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use rand::{rngs::StdRng, SeedableRng};
+
+  fn picks(selection: Selection, weights: &[f64], count: usize) -> Vec<Option<usize>> {
+    let selector = Selector::new(vec![], HashMap::new(), selection);
+    let mut rng = StdRng::seed_from_u64(0);
+
+    (0..count).map(|_| selector.choose(weights, &mut rng)).collect()
+  }
+
+  #[test]
+  fn round_robin_interleaves_picks_smoothly() {
+    let order = picks(Selection::RoundRobin, &[5.0, 1.0, 1.0], 14);
+
+    assert_eq!(order, [0, 0, 1, 0, 2, 0, 0, 0, 0, 1, 0, 2, 0, 0].iter().map(|idx| Some(*idx)).collect::<Vec<_>>());
+  }
+
+  #[test]
+  fn round_robin_skips_upstreams_without_weight() {
+    let order = picks(Selection::RoundRobin, &[2.0, 0.0, 1.0], 6);
+
+    assert_eq!(order, [0, 2, 0, 0, 2, 0].iter().map(|idx| Some(*idx)).collect::<Vec<_>>());
+  }
+
+  #[test]
+  fn random_follows_the_weights() {
+    let mut counts = [0; 3];
+
+    for idx in picks(Selection::Random, &[3.0, 0.0, 1.0], 4000) {
+      counts[idx.unwrap()] += 1;
+    }
+
+    assert_eq!(counts[1], 0);
+    assert!((2800..3200).contains(&counts[0]), "{:?}", counts);
+  }
+
+  #[test]
+  fn nothing_is_picked_without_weight() {
+    for selection in [Selection::Random, Selection::RoundRobin] {
+      assert_eq!(picks(selection, &[0.0, 0.0], 3), vec![None; 3]);
+      assert_eq!(picks(selection, &[], 1), vec![None]);
+    }
+  }
+}
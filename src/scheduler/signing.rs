@@ -0,0 +1,285 @@
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use percent_encoding::{percent_decode_str, percent_encode};
+use reqwest::blocking::Request;
+use reqwest::header::{HeaderName, HeaderValue};
+use reqwest::Url;
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::specs::{Component, HmacAlgorithm, Signing};
+use crate::template::UNRESERVED;
+
+// Sign a request once it is built, so the signature covers its final URL,
+// headers and body.
+pub fn sign(signing: &Signing, request: &mut Request) -> Result<(), String> {
+  sign_at(signing, request, Utc::now())
+}
+
+// Same as `sign`, with the signing time given.
+fn sign_at(signing: &Signing, request: &mut Request, now: DateTime<Utc>) -> Result<(), String> {
+  match signing {
+    Signing::Hmac {
+      secret,
+      algorithm,
+      header,
+      prefix,
+      base64,
+      components,
+    } => {
+      let mut message = vec![];
+
+      for (idx, component) in components.iter().enumerate() {
+        if idx > 0 {
+          message.push(b'\n');
+        }
+
+        match component {
+          Component::Method => message.extend_from_slice(request.method().as_str().as_bytes()),
+          Component::Path => message.extend_from_slice(path_and_query(request).as_bytes()),
+          Component::Date => message.extend_from_slice(date(request, now)?.as_bytes()),
+          Component::Body => message.extend_from_slice(body(request).ok_or("cannot sign a streamed body")?),
+          Component::Header(name) => message.extend_from_slice(request.headers().get(name.as_str()).map(HeaderValue::as_bytes).unwrap_or_default()),
+        }
+      }
+
+      let signature = match algorithm {
+        HmacAlgorithm::Sha256 => hmac::<Hmac<Sha256>>(secret.as_bytes(), &message),
+        HmacAlgorithm::Sha512 => hmac::<Hmac<Sha512>>(secret.as_bytes(), &message),
+      };
+
+      let signature = match base64 {
+        true => base64::engine::general_purpose::STANDARD.encode(signature),
+        false => hex::encode(signature),
+      };
+
+      set_header(request, header, &format!("{}{}", prefix, signature))
+    }
+
+    Signing::AwsSigv4 {
+      access_key,
+      secret_key,
+      region,
+      service,
+      session_token,
+    } => {
+      let timestamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+      let day = now.format("%Y%m%d").to_string();
+
+      // Streamed bodies, such as multipart forms, cannot be hashed beforehand
+      let payload = body(request).map_or_else(|| "UNSIGNED-PAYLOAD".to_owned(), |body| hex::encode(Sha256::digest(body)));
+
+      let url = request.url();
+      let host = match url.port() {
+        Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_owned(),
+      };
+
+      let mut headers = vec![("host", host), ("x-amz-content-sha256", payload.clone()), ("x-amz-date", timestamp.clone())];
+
+      if let Some(token) = session_token {
+        headers.push(("x-amz-security-token", token.to_owned()));
+      }
+
+      let signed = headers.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(";");
+      let canonical = canonical_request(request.method().as_str(), url, &headers, &payload);
+
+      let scope = format!("{}/{}/{}/aws4_request", day, region, service);
+      let message = string_to_sign(&timestamp, &scope, &canonical);
+      let signature = hex::encode(hmac::<Hmac<Sha256>>(&signing_key(secret_key, &day, region, service), message.as_bytes()));
+
+      for (name, value) in headers.iter().skip(1) {
+        set_header(request, name, value)?;
+      }
+
+      set_header(
+        request,
+        "Authorization",
+        &format!("AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}", access_key, scope, signed, signature),
+      )
+    }
+  }
+}
+
+// Canonical form of a request for AWS Signature Version 4, `headers` being
+// lowercase and sorted by name.
+fn canonical_request(method: &str, url: &Url, headers: &[(&str, String)], payload: &str) -> String {
+  let path = url
+    .path()
+    .split('/')
+    .map(|segment| encode(&percent_decode_str(segment).decode_utf8_lossy()))
+    .collect::<Vec<_>>()
+    .join("/");
+
+  let mut query = url.query_pairs().map(|(key, value)| format!("{}={}", encode(&key), encode(&value))).collect::<Vec<_>>();
+  query.sort();
+
+  format!(
+    "{}\n{}\n{}\n{}\n{}\n{}",
+    method,
+    path,
+    query.join("&"),
+    headers.iter().map(|(name, value)| format!("{}:{}\n", name, value.trim())).collect::<String>(),
+    headers.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(";"),
+    payload
+  )
+}
+
+fn string_to_sign(timestamp: &str, scope: &str, canonical: &str) -> String {
+  format!("AWS4-HMAC-SHA256\n{}\n{}\n{}", timestamp, scope, hex::encode(Sha256::digest(canonical.as_bytes())))
+}
+
+// Key derived from the secret key for the day, region and service of a scope.
+fn signing_key(secret_key: &str, day: &str, region: &str, service: &str) -> Vec<u8> {
+  [region, service, "aws4_request"]
+    .iter()
+    .fold(hmac::<Hmac<Sha256>>(format!("AWS4{}", secret_key).as_bytes(), day.as_bytes()), |key, part| {
+      hmac::<Hmac<Sha256>>(&key, part.as_bytes())
+    })
+}
+
+fn hmac<M: Mac + hmac::digest::KeyInit>(key: &[u8], message: &[u8]) -> Vec<u8> {
+  let mut mac = <M as Mac>::new_from_slice(key).expect("HMAC accepts keys of any size");
+  mac.update(message);
+  mac.finalize().into_bytes().to_vec()
+}
+
+fn encode(value: &str) -> String {
+  percent_encode(value.as_bytes(), UNRESERVED).to_string()
+}
+
+fn path_and_query(request: &Request) -> String {
+  match request.url().query() {
+    Some(query) => format!("{}?{}", request.url().path(), query),
+    None => request.url().path().to_owned(),
+  }
+}
+
+// Body of a request, empty if there is none, or `None` if it is streamed.
+fn body(request: &Request) -> Option<&[u8]> {
+  match request.body() {
+    None => Some(&[]),
+    Some(body) => body.as_bytes(),
+  }
+}
+
+// Date of the request, set to the signing time unless given in the headers.
+fn date(request: &mut Request, now: DateTime<Utc>) -> Result<String, String> {
+  if let Some(date) = request.headers().get("date").and_then(|date| date.to_str().ok()) {
+    return Ok(date.to_owned());
+  }
+
+  let date = now.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+  set_header(request, "Date", &date)?;
+
+  Ok(date)
+}
+
+fn set_header(request: &mut Request, name: &str, value: &str) -> Result<(), String> {
+  let name = HeaderName::from_bytes(name.as_bytes()).map_err(|err| err.to_string())?;
+  let value = HeaderValue::from_str(value).map_err(|err| err.to_string())?;
+
+  request.headers_mut().insert(name, value);
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use chrono::TimeZone;
+  use reqwest::Method;
+
+  const SECRET_KEY: &str = "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY";
+  const LIST_USERS: &str = "https://iam.amazonaws.com/?Action=ListUsers&Version=2010-05-08";
+
+  fn now() -> DateTime<Utc> {
+    Utc.ymd(2015, 8, 30).and_hms(12, 36, 0)
+  }
+
+  fn header(request: &Request, name: &str) -> String {
+    request.headers()[name].to_str().unwrap().to_owned()
+  }
+
+  // RFC 4231, test case 2
+  #[test]
+  fn hmac_matches_rfc_4231() {
+    let message = b"what do ya want for nothing?";
+
+    assert_eq!(hex::encode(hmac::<Hmac<Sha256>>(b"Jefe", message)), "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+    assert_eq!(
+      hex::encode(hmac::<Hmac<Sha512>>(b"Jefe", message)),
+      "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea2505549758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737"
+    );
+  }
+
+  #[test]
+  fn hmac_signs_components_one_per_line() {
+    let signing = Signing::Hmac {
+      secret: "secret".to_owned(),
+      algorithm: HmacAlgorithm::Sha256,
+      header: "X-Signature".to_owned(),
+      prefix: "HMAC ".to_owned(),
+      base64: true,
+      components: vec![Component::Method, Component::Path, Component::Date, Component::Body],
+    };
+
+    let mut request = Request::new(Method::POST, Url::parse("https://example.com/orders?page=2").unwrap());
+    *request.body_mut() = Some(r#"{"id":1}"#.into());
+
+    sign_at(&signing, &mut request, now()).unwrap();
+
+    assert_eq!(header(&request, "date"), "Sun, 30 Aug 2015 12:36:00 GMT");
+    assert_eq!(header(&request, "x-signature"), "HMAC 06O3WzHYOyYQ7fqmUWx8EHxEJdudO9Folb/FQX9KYmQ=");
+  }
+
+  // Example of the AWS Signature Version 4 documentation, signing a ListUsers
+  // request to IAM.
+  #[test]
+  fn sigv4_matches_aws_example() {
+    let headers = [
+      ("content-type", "application/x-www-form-urlencoded; charset=utf-8".to_owned()),
+      ("host", "iam.amazonaws.com".to_owned()),
+      ("x-amz-date", "20150830T123600Z".to_owned()),
+    ];
+
+    let payload = hex::encode(Sha256::digest(b""));
+    let canonical = canonical_request("GET", &Url::parse(LIST_USERS).unwrap(), &headers, &payload);
+
+    assert_eq!(hex::encode(Sha256::digest(canonical.as_bytes())), "f536975d06c0309214f805bb90ccff089219ecd68b2577efef23edd43b7e1a59");
+
+    let key = signing_key(SECRET_KEY, "20150830", "us-east-1", "iam");
+
+    assert_eq!(hex::encode(&key), "c4afb1cc5771d871763a393e44b703571b55cc28424d1a5e86da6ed3c154a4b9");
+
+    let message = string_to_sign("20150830T123600Z", "20150830/us-east-1/iam/aws4_request", &canonical);
+
+    assert_eq!(
+      hex::encode(hmac::<Hmac<Sha256>>(&key, message.as_bytes())),
+      "5d672d79c15b13162d9279b0855cfba6789a8edb4c82c400e06b5924a6f2b5d7"
+    );
+  }
+
+  #[test]
+  fn sigv4_signs_host_payload_and_date() {
+    let signing = Signing::AwsSigv4 {
+      access_key: "AKIDEXAMPLE".to_owned(),
+      secret_key: SECRET_KEY.to_owned(),
+      region: "us-east-1".to_owned(),
+      service: "iam".to_owned(),
+      session_token: None,
+    };
+
+    let mut request = Request::new(Method::GET, Url::parse(LIST_USERS).unwrap());
+
+    sign_at(&signing, &mut request, now()).unwrap();
+
+    assert_eq!(header(&request, "x-amz-date"), "20150830T123600Z");
+    assert_eq!(header(&request, "x-amz-content-sha256"), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+    assert_eq!(
+      header(&request, "authorization"),
+      "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/iam/aws4_request, SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature=65f031d93b4631aedf16a8f7f830cdc8ce2bc5276c307b5a2cc2143d4b68e323"
+    );
+  }
+}
@@ -0,0 +1,310 @@
+use memchr::memmem;
+use rand::Rng;
+use reqwest::Url;
+use socket2::{Domain, Protocol, Type};
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::result::{Failure, Success};
+use crate::specs::{Network, Reply, Scenario, Socket, Transport, Upstream};
+use crate::template::Bindings;
+use crate::{config, util};
+
+use super::dispatcher;
+
+// Outcome of an exchange which did not complete.
+enum Exchange {
+  // Nothing reached the server
+  Unreachable(String),
+  // The payload was sent but the reply could not be read, some bytes of it may
+  // have been received
+  Incomplete(u64, String),
+}
+
+// Send the payload of a TCP or UDP upstream and read its reply, if required,
+// which must match `expect`. The exchange lasts until the reply is complete,
+// within the timeout of the scenario.
+pub fn send<R: Rng>(options: &Arc<config::Options>, scenario: &Scenario, req: &Upstream, socket: &Socket, rng: &mut R) -> Option<Result<Success, Failure>> {
+  let offset = util::elapsed_since(scenario.start);
+
+  let (url, payload) = match render(scenario, req, socket, rng) {
+    Ok(rendered) => rendered,
+    Err(label) => return dispatcher::exhausted(scenario, req, offset, label),
+  };
+
+  let request_desc = format!("SEND {}", url);
+
+  let payload = match socket.hex {
+    false => payload,
+    true => match Socket::decode(&payload) {
+      Ok(payload) => payload,
+      Err(err) => return Some(Failure::global(offset, request_desc, 0, format!("invalid hexadecimal payload: {}", err))),
+    },
+  };
+
+  let addrs = match addresses(&req.network, &url) {
+    Ok(addrs) => addrs,
+    Err(reason) => return Some(Failure::global(offset, request_desc, 0, reason)),
+  };
+
+  let start = util::current_epoch_ms();
+  let deadline = Instant::now() + Duration::from_secs(scenario.options.timeout);
+
+  let exchanged = match socket.transport {
+    Transport::Tcp => tcp(scenario, &req.network, socket, &addrs, &payload, deadline),
+    Transport::Udp => udp(&req.network, socket, &addrs, &payload, deadline),
+  };
+
+  let duration = util::current_epoch_ms() - start;
+
+  if options.verbose {
+    util::write_flush(if exchanged.is_ok() { "·" } else { "!" });
+  }
+
+  let protocol = match socket.transport {
+    Transport::Tcp => "TCP",
+    Transport::Udp => "UDP",
+  };
+
+  let result = match exchanged {
+    Ok(reply) => match socket.expect {
+      Some(ref expect) if !expect.is_match(&reply) => Failure::http(offset, request_desc, duration, 0, protocol, reply.len() as u64, format!("reply does not match '{}'", expect.as_str())),
+      _ => Success::new(offset, request_desc, duration, 0, protocol, reply.len() as u64),
+    },
+
+    Err(Exchange::Unreachable(reason)) => Failure::global(offset, request_desc, duration, reason),
+    Err(Exchange::Incomplete(bytes, reason)) => Failure::http(offset, request_desc, duration, 0, protocol, bytes, reason),
+  };
+
+  Some(result)
+}
+
+// Interpolate the URL and the payload of an upstream, sharing their values.
+fn render<R: Rng>(scenario: &Scenario, req: &Upstream, socket: &Socket, rng: &mut R) -> Result<(String, Vec<u8>), String> {
+  let mut bindings = Bindings::default();
+  let url = req.url.render(scenario, &mut bindings, rng)?;
+  let payload = socket.payload.render_bytes(scenario, &mut bindings, rng)?;
+
+  Ok((url, payload))
+}
+
+// Addresses of the host of a `tcp://` or `udp://` URL, unless it is pinned.
+fn addresses(network: &Network, url: &str) -> Result<Vec<SocketAddr>, String> {
+  let parsed = Url::parse(url).map_err(|err| format!("invalid URL '{}': {}", url, err))?;
+  let host = parsed.host_str().unwrap_or_default().trim_start_matches('[').trim_end_matches(']');
+  let port = parsed.port().ok_or_else(|| format!("no port in URL '{}'", url))?;
+
+  match network.resolve.iter().find(|(name, _)| name == host) {
+    Some((_, addr)) => Ok(vec![SocketAddr::new(*addr, port)]),
+    None => Ok((host, port).to_socket_addrs().map_err(|err| format!("could not resolve '{}': {}", host, err))?.collect()),
+  }
+}
+
+// Open a connection from the next source address of the upstream, if any, to
+// the first address of the host which accepts it.
+fn tcp(scenario: &Scenario, network: &Network, socket: &Socket, addrs: &[SocketAddr], payload: &[u8], deadline: Instant) -> Result<Vec<u8>, Exchange> {
+  let source = network.source();
+  let mut stream = Err(unreachable(source));
+
+  // Only the addresses of the family of the source address can be reached from it
+  for addr in addrs.iter().filter(|addr| source.is_none_or(|source| source.is_ipv4() == addr.is_ipv4())) {
+    stream = connect(*addr, source, deadline.saturating_duration_since(Instant::now())).map_err(|err| err.to_string());
+
+    if stream.is_ok() {
+      break;
+    }
+  }
+
+  let mut stream = match stream {
+    Ok(stream) => {
+      scenario.connections.opened.fetch_add(1, Ordering::SeqCst);
+      stream
+    }
+
+    Err(reason) => {
+      scenario.connections.failed.fetch_add(1, Ordering::SeqCst);
+      return Err(Exchange::Unreachable(reason));
+    }
+  };
+
+  let _ = stream.set_write_timeout(Some(deadline.saturating_duration_since(Instant::now()).max(Duration::from_millis(1))));
+
+  stream.write_all(payload).map_err(|err| Exchange::Incomplete(0, format!("could not send the payload: {}", err)))?;
+
+  reply(&socket.reply, deadline, true, |buffer, timeout| {
+    stream.set_read_timeout(Some(timeout))?;
+    stream.read(buffer)
+  })
+}
+
+// Connect to an address from the source address, if any.
+pub fn connect(addr: SocketAddr, source: Option<IpAddr>, timeout: Duration) -> io::Result<TcpStream> {
+  let socket = socket2::Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+
+  if let Some(source) = source {
+    socket.bind(&SocketAddr::new(source, 0).into())?;
+  }
+
+  socket.connect_timeout(&addr.into(), timeout.max(Duration::from_millis(1)))?;
+  socket.set_tcp_nodelay(true)?;
+
+  Ok(socket.into())
+}
+
+// Send the payload in a single datagram, from the next source address of the
+// upstream, if any, to the first address of the host.
+fn udp(network: &Network, socket: &Socket, addrs: &[SocketAddr], payload: &[u8], deadline: Instant) -> Result<Vec<u8>, Exchange> {
+  let source = network.source();
+
+  let addr = addrs
+    .iter()
+    .find(|addr| source.is_none_or(|source| source.is_ipv4() == addr.is_ipv4()))
+    .ok_or_else(|| Exchange::Unreachable(unreachable(source)))?;
+
+  let local = match (source, addr) {
+    (Some(source), _) => SocketAddr::new(source, 0),
+    (None, SocketAddr::V4(_)) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+    (None, SocketAddr::V6(_)) => SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0),
+  };
+
+  let udp = UdpSocket::bind(local).map_err(|err| Exchange::Unreachable(format!("could not bind {}: {}", local.ip(), err)))?;
+
+  udp.connect(addr).map_err(|err| Exchange::Unreachable(err.to_string()))?;
+  udp.send(payload).map_err(|err| Exchange::Unreachable(format!("could not send the payload: {}", err)))?;
+
+  reply(&socket.reply, deadline, false, |buffer, timeout| {
+    udp.set_read_timeout(Some(timeout))?;
+    udp.recv(buffer)
+  })
+}
+
+pub fn unreachable(source: Option<IpAddr>) -> String {
+  match source {
+    Some(source) => format!("no address of the host can be reached from {}", source),
+    None => "no address for the host".to_owned(),
+  }
+}
+
+// Read a reply until its delimiter, included, or its number of bytes, from the
+// data given by `receive` before the deadline. Receiving nothing means the
+// connection was closed on a stream, but is an empty datagram otherwise.
+fn reply<F>(expected: &Reply, deadline: Instant, stream: bool, mut receive: F) -> Result<Vec<u8>, Exchange>
+where
+  F: FnMut(&mut [u8], Duration) -> io::Result<usize>,
+{
+  let mut reply = vec![];
+  let mut buffer = vec![0; 64 * 1024];
+
+  loop {
+    match expected {
+      Reply::Ignore => return Ok(reply),
+
+      Reply::Delimiter(delimiter) => {
+        if let Some(position) = memmem::find(&reply, delimiter) {
+          reply.truncate(position + delimiter.len());
+          return Ok(reply);
+        }
+      }
+
+      Reply::Bytes(bytes) => {
+        if reply.len() as u64 >= *bytes {
+          reply.truncate(*bytes as usize);
+          return Ok(reply);
+        }
+      }
+    }
+
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    let received = reply.len() as u64;
+
+    if remaining.is_zero() {
+      return Err(Exchange::Incomplete(received, "no complete reply before the timeout".to_owned()));
+    }
+
+    match receive(&mut buffer, remaining) {
+      Ok(0) if stream => return Err(Exchange::Incomplete(received, "connection closed by the server".to_owned())),
+      Ok(size) => reply.extend_from_slice(&buffer[..size]),
+      Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+      Err(err) if err.kind() == ErrorKind::WouldBlock || err.kind() == ErrorKind::TimedOut => return Err(Exchange::Incomplete(received, "no complete reply before the timeout".to_owned())),
+      Err(err) => return Err(Exchange::Incomplete(received, err.to_string())),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Reads a reply from `chunks`, received one per call, the end of the chunks
+  // being an error of kind `end`.
+  fn read(expected: Reply, stream: bool, chunks: &[&str], end: ErrorKind) -> Result<String, (u64, String)> {
+    let mut chunks = chunks.iter();
+    let deadline = Instant::now() + Duration::from_secs(5);
+
+    let receive = |buffer: &mut [u8], _: Duration| match chunks.next() {
+      Some(chunk) => {
+        buffer[..chunk.len()].copy_from_slice(chunk.as_bytes());
+        Ok(chunk.len())
+      }
+      None => Err(io::Error::from(end)),
+    };
+
+    match reply(&expected, deadline, stream, receive) {
+      Ok(reply) => Ok(String::from_utf8(reply).unwrap()),
+      Err(Exchange::Incomplete(received, reason)) => Err((received, reason)),
+      Err(Exchange::Unreachable(reason)) => Err((0, reason)),
+    }
+  }
+
+  fn delimiter(delimiter: &str) -> Reply {
+    Reply::Delimiter(delimiter.as_bytes().to_vec())
+  }
+
+  #[test]
+  fn reads_until_the_delimiter_included() {
+    assert_eq!(read(delimiter("\r\n"), true, &["HELLO\r\nrest"], ErrorKind::Other), Ok("HELLO\r\n".to_owned()));
+    assert_eq!(read(delimiter("\r\n"), true, &["HEL", "LO\r", "\nrest"], ErrorKind::Other), Ok("HELLO\r\n".to_owned()));
+    assert_eq!(read(delimiter("END"), true, &["a", "E", "N", "D"], ErrorKind::Other), Ok("aEND".to_owned()));
+  }
+
+  #[test]
+  fn reads_a_number_of_bytes() {
+    assert_eq!(read(Reply::Bytes(5), true, &["ab", "cdef"], ErrorKind::Other), Ok("abcde".to_owned()));
+    assert_eq!(read(Reply::Bytes(2), true, &["ab"], ErrorKind::Other), Ok("ab".to_owned()));
+  }
+
+  #[test]
+  fn ignored_replies_are_not_read() {
+    assert_eq!(read(Reply::Ignore, true, &[], ErrorKind::Other), Ok(String::new()));
+  }
+
+  #[test]
+  fn closed_streams_end_incomplete_replies() {
+    assert_eq!(read(delimiter("\n"), true, &["abc", ""], ErrorKind::Other), Err((3, "connection closed by the server".to_owned())));
+    assert_eq!(read(Reply::Bytes(4), true, &["abc", ""], ErrorKind::Other), Err((3, "connection closed by the server".to_owned())));
+  }
+
+  #[test]
+  fn empty_datagrams_are_skipped() {
+    assert_eq!(read(delimiter("\n"), false, &["", "ok\n"], ErrorKind::Other), Ok("ok\n".to_owned()));
+  }
+
+  #[test]
+  fn timeouts_end_incomplete_replies() {
+    let timeout = Err((2, "no complete reply before the timeout".to_owned()));
+
+    assert_eq!(read(delimiter("\n"), true, &["ab"], ErrorKind::WouldBlock), timeout);
+    assert_eq!(read(delimiter("\n"), true, &["ab"], ErrorKind::TimedOut), timeout);
+    assert_eq!(read(Reply::Bytes(3), true, &["ab"], ErrorKind::ConnectionReset), Err((2, "connection reset".to_owned())));
+  }
+
+  #[test]
+  fn replies_are_not_read_past_the_deadline() {
+    let result = reply(&Reply::Bytes(1), Instant::now(), true, |_: &mut [u8], _| -> io::Result<usize> { panic!("read past the deadline") });
+
+    assert!(matches!(result, Err(Exchange::Incomplete(0, _))));
+  }
+}
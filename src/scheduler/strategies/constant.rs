@@ -2,14 +2,14 @@ use colored::*;
 
 use std::fmt;
 
-use crate::scheduler::Schedulable;
+use crate::scheduler::{Schedulable, Selector};
 use crate::util;
 
 #[derive(Debug)]
 pub struct Constant {
-  pub upstreams: Vec<String>,
+  pub selector: Selector,
   pub duration: u64,
-  pub count: u64,
+  pub count: f64,
   pub interval: u64,
 }
 
@@ -24,20 +24,16 @@ impl fmt::Display for Constant {
       self.duration.to_string().bold()
     )?;
 
-    if !self.upstreams.is_empty() {
-      writeln!(f, "    {} {}", "upstreams:".dimmed(), self.upstreams.join(", "))?;
-    }
-
-    Ok(())
+    write!(f, "{}", self.selector)
   }
 }
 
 impl Schedulable for Constant {
-  fn schedule(&self, start: f64) -> Option<(u64, u64, Vec<String>)> {
+  fn schedule(&self, start: f64) -> Option<(f64, u64, Option<&Selector>)> {
     if util::elapsed_since(start) >= self.duration {
       None
     } else {
-      Some((self.count, self.interval, self.upstreams.clone()))
+      Some((self.count, self.interval, Some(&self.selector)))
     }
   }
 }
@@ -1,16 +1,17 @@
 use colored::*;
 
+use std::convert::TryFrom;
 use std::fmt;
 
-use crate::scheduler::Schedulable;
+use crate::scheduler::{Schedulable, Selector};
 use crate::util;
 
 #[derive(Debug)]
 pub struct DoubleEvery {
-  pub upstreams: Vec<String>,
+  pub selector: Selector,
   pub duration: u64,
   pub period: u64,
-  pub count: u64,
+  pub count: f64,
   pub interval: u64,
 }
 
@@ -26,24 +27,22 @@ impl fmt::Display for DoubleEvery {
       self.period.to_string().bold()
     )?;
 
-    if !self.upstreams.is_empty() {
-      writeln!(f, "    {} {}", "upstreams:".dimmed(), self.upstreams.join(", "))?;
-    }
-
-    Ok(())
+    write!(f, "{}", self.selector)
   }
 }
 
 impl Schedulable for DoubleEvery {
-  fn schedule(&self, start: f64) -> Option<(u64, u64, Vec<String>)> {
+  fn schedule(&self, start: f64) -> Option<(f64, u64, Option<&Selector>)> {
     let elapsed = util::current_epoch() - start;
 
     if util::elapsed_since(start) >= self.duration {
       None
     } else {
-      let laps = 2u64.pow(elapsed as u32 / self.period as u32);
+      // The count stops doubling once it reaches the largest number of laps
+      let doublings = elapsed as u64 / self.period;
+      let laps = u32::try_from(doublings).ok().and_then(|doublings| 2u64.checked_pow(doublings)).unwrap_or(u64::MAX);
 
-      Some((self.count * laps, self.interval, self.upstreams.clone()))
+      Some((self.count * laps as f64, self.interval, Some(&self.selector)))
     }
   }
 }
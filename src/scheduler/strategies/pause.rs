@@ -2,7 +2,7 @@ use colored::*;
 
 use std::fmt;
 
-use crate::scheduler::Schedulable;
+use crate::scheduler::{Schedulable, Selector};
 use crate::util;
 
 #[derive(Debug)]
@@ -20,11 +20,11 @@ impl fmt::Display for Pause {
 }
 
 impl Schedulable for Pause {
-  fn schedule(&self, start: f64) -> Option<(u64, u64, Vec<String>)> {
+  fn schedule(&self, start: f64) -> Option<(f64, u64, Option<&Selector>)> {
     if util::elapsed_since(start) >= self.duration {
       None
     } else {
-      Some((0, 1, vec![]))
+      Some((0.0, 1, None))
     }
   }
 }
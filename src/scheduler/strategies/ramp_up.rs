@@ -2,16 +2,16 @@ use colored::*;
 
 use std::fmt;
 
-use crate::scheduler::Schedulable;
+use crate::scheduler::{Schedulable, Selector};
 use crate::util;
 
 #[derive(Debug)]
 pub struct RampUp {
-  pub upstreams: Vec<String>,
+  pub selector: Selector,
   pub duration: u64,
   pub interval: u64,
-  pub from: u64,
-  pub to: u64,
+  pub from: f64,
+  pub to: f64,
 }
 
 impl fmt::Display for RampUp {
@@ -26,24 +26,20 @@ impl fmt::Display for RampUp {
       self.duration.to_string().bold()
     )?;
 
-    if !self.upstreams.is_empty() {
-      writeln!(f, "    {} {}", "upstreams:".dimmed(), self.upstreams.join(", "))?;
-    }
-
-    Ok(())
+    write!(f, "{}", self.selector)
   }
 }
 
 impl Schedulable for RampUp {
-  fn schedule(&self, start: f64) -> Option<(u64, u64, Vec<String>)> {
+  fn schedule(&self, start: f64) -> Option<(f64, u64, Option<&Selector>)> {
     let elapsed = util::current_epoch() - start;
 
     if util::elapsed_since(start) >= self.duration {
       None
     } else {
-      let laps = ((self.to - self.from) as f64 * (elapsed / self.duration as f64)) as u64 + self.from;
+      let laps = (self.to - self.from) * (elapsed / self.duration as f64) + self.from;
 
-      Some((laps, self.interval, self.upstreams.clone()))
+      Some((laps, self.interval, Some(&self.selector)))
     }
   }
 }
@@ -2,7 +2,7 @@ use colored::*;
 
 use std::fmt;
 
-use crate::scheduler::{strategies, Schedulable};
+use crate::scheduler::{strategies, Schedulable, Selection, Selector};
 use crate::util;
 
 #[derive(Debug)]
@@ -13,6 +13,22 @@ pub struct SteppedConstant {
 impl fmt::Display for SteppedConstant {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     writeln!(f, "  - {} {}", "type:".dimmed(), "SteppedConstant".bold())?;
+
+    // Weights and selection mode are shared by all steps
+    if let Some(step) = self.steps.first() {
+      let weights = &step.selector.weights;
+
+      if !weights.is_empty() {
+        let weights = weights.iter().map(|(name, weight)| format!("{}={}", name, weight)).collect::<Vec<String>>();
+
+        writeln!(f, "    {} {}", "weights:".dimmed(), weights.join(", "))?;
+      }
+
+      if step.selector.selection == Selection::RoundRobin {
+        writeln!(f, "    {} round-robin", "selection:".dimmed())?;
+      }
+    }
+
     writeln!(f, "    {}", "steps:".dimmed())?;
 
     for (idx, step) in self.steps.iter().enumerate() {
@@ -24,6 +40,10 @@ impl fmt::Display for SteppedConstant {
         step.interval.to_string().bold(),
         step.duration.to_string().bold()
       )?;
+
+      if !step.selector.upstreams.is_empty() {
+        writeln!(f, "         {} {}", "upstreams:".dimmed(), step.selector.upstreams.join(", "))?;
+      }
     }
 
     Ok(())
@@ -31,7 +51,7 @@ impl fmt::Display for SteppedConstant {
 }
 
 impl Schedulable for SteppedConstant {
-  fn schedule(&self, start: f64) -> Option<(u64, u64, Vec<String>)> {
+  fn schedule(&self, start: f64) -> Option<(f64, u64, Option<&Selector>)> {
     let elapsed = util::current_epoch() - start;
     let mut offset = 0;
 
@@ -42,9 +62,6 @@ impl Schedulable for SteppedConstant {
       found
     });
 
-    match threshold {
-      None => None,
-      Some(s) => Some((s.count, s.interval, vec![])),
-    }
+    threshold.map(|s| (s.count, s.interval, Some(&s.selector)))
   }
 }
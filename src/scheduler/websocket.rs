@@ -0,0 +1,299 @@
+use base64::Engine;
+use native_tls::TlsConnector;
+use rand::Rng;
+use regex::Regex;
+use reqwest::cookie::CookieStore;
+use reqwest::Url;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tungstenite::client::{client_with_config, IntoClientRequest};
+use tungstenite::handshake::HandshakeError;
+use tungstenite::http::header::{HeaderName, HeaderValue, AUTHORIZATION, COOKIE};
+use tungstenite::protocol::CloseFrame;
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Connector, Error, Message, WebSocket};
+
+use crate::result::{Failure, Success};
+use crate::specs::{Scenario, Upstream, Websocket};
+use crate::template::Bindings;
+use crate::{config, util};
+
+use super::{dispatcher, socket};
+
+type Socket = WebSocket<MaybeTlsStream<TcpStream>>;
+
+// Open a session on a WebSocket upstream, recording the time taken to connect,
+// then the round-trip time of every message awaiting a reply. The session ends
+// early, with a failure, when the server closes the connection.
+pub fn session<R: Rng>(options: &Arc<config::Options>, scenario: &Scenario, req: &Upstream, websocket: &Websocket, user: Option<usize>, rng: &mut R, tx: &Sender<Result<Success, Failure>>) {
+  let offset = util::elapsed_since(scenario.start);
+
+  // Values bound by the URL and headers are kept for the whole session
+  let mut bindings = Bindings::default();
+
+  let (url, headers) = match render(scenario, req, user, &mut bindings, rng) {
+    Ok(rendered) => rendered,
+    Err(label) => {
+      if let Some(result) = dispatcher::exhausted(scenario, req, offset, label) {
+        tx.send(result).unwrap();
+      }
+
+      return;
+    }
+  };
+
+  let start = util::current_epoch_ms();
+  let connected = connect(scenario, req, websocket, &url, headers);
+  let duration = util::current_epoch_ms() - start;
+
+  if options.verbose {
+    util::write_flush(if connected.is_ok() { "·" } else { "!" });
+  }
+
+  let mut socket = match connected {
+    Ok(socket) => {
+      scenario.connections.opened.fetch_add(1, Ordering::SeqCst);
+      tx.send(Success::new(offset, format!("CONNECT {}", url), duration, 101, "WebSocket", 0)).unwrap();
+
+      socket
+    }
+
+    Err((code, reason)) => {
+      scenario.connections.failed.fetch_add(1, Ordering::SeqCst);
+
+      let result = match code {
+        0 => Failure::global(offset, format!("CONNECT {}", url), duration, reason),
+        code => Failure::http(offset, format!("CONNECT {}", url), duration, code, "", 0, reason),
+      };
+
+      tx.send(result).unwrap();
+      return;
+    }
+  };
+
+  let request = format!("MESSAGE {}", url);
+
+  for round in 0..websocket.repeat {
+    for (idx, message) in websocket.messages.iter().enumerate() {
+      if round > 0 || idx > 0 {
+        thread::sleep(Duration::from_secs(websocket.interval));
+      }
+
+      let text = match message.send.render(scenario, &mut bindings.clone(), rng) {
+        Ok(text) => text,
+        Err(label) => {
+          if let Some(result) = dispatcher::exhausted(scenario, req, util::elapsed_since(scenario.start), label) {
+            tx.send(result).unwrap();
+          }
+
+          let _ = socket.close(None);
+          return;
+        }
+      };
+
+      let offset = util::elapsed_since(scenario.start);
+
+      // Replies to the previous messages must not be taken for this one's
+      if let Err(reason) = skip(&mut socket) {
+        tx.send(Failure::global(offset, request, 0, reason)).unwrap();
+        return;
+      }
+
+      let start = util::current_epoch_ms();
+
+      if let Err(err) = socket.send(Message::Text(text)) {
+        tx.send(Failure::global(offset, request, util::current_epoch_ms() - start, disconnected(&err))).unwrap();
+        return;
+      }
+
+      let expect = match message.expect {
+        Some(ref expect) => expect,
+        None => continue,
+      };
+
+      match reply(&mut socket, expect, message.timeout) {
+        Ok(bytes) => tx.send(Success::new(offset, request.clone(), util::current_epoch_ms() - start, 0, "", bytes)).unwrap(),
+
+        Err(None) => {
+          let reason = format!("no matching reply within {}s", message.timeout);
+          tx.send(Failure::global(offset, request.clone(), util::current_epoch_ms() - start, reason)).unwrap();
+        }
+
+        Err(Some(reason)) => {
+          tx.send(Failure::global(offset, request, util::current_epoch_ms() - start, reason)).unwrap();
+          return;
+        }
+      }
+    }
+  }
+
+  // Wait for the server to acknowledge the closing of the connection
+  if socket.close(None).is_ok() {
+    set_read_timeout(&mut socket, Duration::from_secs(scenario.options.timeout));
+    while socket.read().is_ok() {}
+  }
+}
+
+// URL and handshake headers of a session, with the cookies of its virtual user.
+fn render<R: Rng>(scenario: &Scenario, req: &Upstream, user: Option<usize>, bindings: &mut Bindings, rng: &mut R) -> Result<(String, Vec<(String, String)>), String> {
+  let url = req.url.render(scenario, bindings, rng)?;
+  let mut headers = vec![];
+
+  if let (Some(user), Ok(parsed)) = (user, Url::parse(&url)) {
+    let cookies = scenario.users[user].jar.cookies(&parsed).and_then(|cookies| cookies.to_str().ok().map(str::to_owned));
+
+    if let Some(ref cookies) = cookies {
+      headers.push((COOKIE.to_string(), cookies.to_owned()));
+    }
+
+    bindings.set_cookies(cookies);
+  }
+
+  if let Some(ref basic) = req.basic {
+    let credentials = base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", basic.username, basic.password));
+    headers.push((AUTHORIZATION.to_string(), format!("Basic {}", credentials)));
+  }
+
+  for (key, value) in &req.headers {
+    headers.push((key.to_owned(), value.render(scenario, bindings, rng)?));
+  }
+
+  Ok((url, headers))
+}
+
+// Open the connection, from the next source address of the upstream if any,
+// and perform the handshake, failing with the status code of the response, if
+// any, and a reason.
+fn connect(scenario: &Scenario, req: &Upstream, websocket: &Websocket, url: &str, headers: Vec<(String, String)>) -> Result<Socket, (u16, String)> {
+  let parsed = Url::parse(url).map_err(|err| (0, format!("invalid URL '{}': {}", url, err)))?;
+  let host = parsed.host_str().unwrap_or_default();
+  let port = parsed.port_or_known_default().unwrap_or(80);
+  let timeout = Duration::from_secs(scenario.options.timeout);
+  let source = req.network.source();
+
+  let addrs = match req.network.resolve.iter().find(|(name, _)| name == host) {
+    Some((_, addr)) => vec![SocketAddr::new(*addr, port)],
+    None => (host, port).to_socket_addrs().map_err(|err| (0, format!("could not resolve '{}': {}", host, err)))?.collect(),
+  };
+
+  let mut stream = Err((0, socket::unreachable(source)));
+
+  // Only the addresses of the family of the source address can be reached from it
+  for addr in addrs.iter().filter(|addr| source.is_none_or(|source| source.is_ipv4() == addr.is_ipv4())) {
+    stream = socket::connect(*addr, source, timeout).map_err(|err| (0, err.to_string()));
+
+    if stream.is_ok() {
+      break;
+    }
+  }
+
+  let stream = stream?;
+  let _ = stream.set_read_timeout(Some(timeout));
+  let _ = stream.set_write_timeout(Some(timeout));
+
+  let mut request = url.into_client_request().map_err(|err| (0, err.to_string()))?;
+
+  for (key, value) in headers {
+    let name = HeaderName::from_bytes(key.as_bytes()).map_err(|err| (0, err.to_string()))?;
+    let value = HeaderValue::from_str(&value).map_err(|err| (0, err.to_string()))?;
+
+    request.headers_mut().append(name, value);
+  }
+
+  // The TLS handshake is performed beforehand when another server name than
+  // the host of the URL is sent
+  let handshake = match websocket.sni {
+    Some(ref sni) if parsed.scheme() == "wss" => {
+      let connector = match websocket.tls {
+        Some(ref connector) => connector.clone(),
+        None => TlsConnector::new().map_err(|err| (0, err.to_string()))?,
+      };
+
+      match connector.connect(sni, stream) {
+        Ok(stream) => client_with_config(request, MaybeTlsStream::NativeTls(stream), None),
+        Err(native_tls::HandshakeError::Failure(err)) => return Err((0, format!("TLS handshake failed: {}", err))),
+        Err(native_tls::HandshakeError::WouldBlock(_)) => return Err((0, "timed out".to_owned())),
+      }
+    }
+
+    _ => tungstenite::client_tls_with_config(request, stream, None, websocket.tls.clone().map(Connector::NativeTls)),
+  };
+
+  match handshake {
+    Ok((socket, _)) => Ok(socket),
+    Err(HandshakeError::Failure(Error::Http(response))) => Err((response.status().as_u16(), "connection upgrade refused".to_owned())),
+    Err(HandshakeError::Failure(Error::Tls(err))) => Err((0, format!("TLS handshake failed: {}", err))),
+    Err(HandshakeError::Failure(err)) => Err((0, err.to_string())),
+    Err(HandshakeError::Interrupted(_)) => Err((0, "timed out".to_owned())),
+  }
+}
+
+// Wait for a message matching `expect`, returning its size. Other messages are
+// skipped, and pings answered. Fails with no reason when no matching message
+// was received in time, or with the reason the connection was closed.
+fn reply(socket: &mut Socket, expect: &Regex, timeout: u64) -> Result<u64, Option<String>> {
+  let deadline = Instant::now() + Duration::from_secs(timeout);
+
+  loop {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+
+    if remaining.is_zero() {
+      return Err(None);
+    }
+
+    set_read_timeout(socket, remaining);
+
+    match socket.read() {
+      Ok(Message::Text(text)) if expect.is_match(&text) => return Ok(text.len() as u64),
+      Ok(Message::Binary(data)) if expect.is_match(&String::from_utf8_lossy(&data)) => return Ok(data.len() as u64),
+      Ok(Message::Close(frame)) => return Err(Some(closed(frame))),
+      Ok(_) => continue,
+      Err(Error::Io(ref err)) if err.kind() == ErrorKind::WouldBlock || err.kind() == ErrorKind::TimedOut => return Err(None),
+      Err(err) => return Err(Some(disconnected(&err))),
+    }
+  }
+}
+
+// Skip the messages already received, failing if the connection was closed.
+fn skip(socket: &mut Socket) -> Result<(), String> {
+  set_read_timeout(socket, Duration::from_millis(1));
+
+  loop {
+    match socket.read() {
+      Ok(Message::Close(frame)) => return Err(closed(frame)),
+      Ok(_) => continue,
+      Err(Error::Io(ref err)) if err.kind() == ErrorKind::WouldBlock || err.kind() == ErrorKind::TimedOut => return Ok(()),
+      Err(err) => return Err(disconnected(&err)),
+    }
+  }
+}
+
+fn set_read_timeout(socket: &mut Socket, timeout: Duration) {
+  let stream = match socket.get_mut() {
+    MaybeTlsStream::Plain(stream) => stream,
+    MaybeTlsStream::NativeTls(stream) => stream.get_mut(),
+    _ => return,
+  };
+
+  let _ = stream.set_read_timeout(Some(timeout));
+}
+
+fn closed(frame: Option<CloseFrame>) -> String {
+  match frame {
+    Some(frame) if frame.reason.is_empty() => format!("connection closed by the server ({})", u16::from(frame.code)),
+    Some(frame) => format!("connection closed by the server ({}: {})", u16::from(frame.code), frame.reason),
+    None => "connection closed by the server".to_owned(),
+  }
+}
+
+fn disconnected(err: &Error) -> String {
+  match err {
+    Error::ConnectionClosed | Error::AlreadyClosed => "connection closed by the server".to_owned(),
+    err => format!("connection lost: {}", err),
+  }
+}
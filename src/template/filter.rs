@@ -0,0 +1,106 @@
+use base64::Engine;
+use percent_encoding::{percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+// Characters left as is by the urlencode filter, as in RFC 3986.
+pub const UNRESERVED: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+// Step of the pipeline applied to the value of an expression, as in
+// `{user|default:anonymous|urlencode}`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+  Default(String),
+  UrlEncode,
+  Base64,
+  JsonEscape,
+  Upper,
+  Lower,
+  // Keeps the value a string in JSON bodies, the value is left as is
+  String,
+}
+
+impl Filter {
+  pub fn parse(name: &str, arg: Option<&str>) -> Result<Filter, String> {
+    let filter = match name {
+      "default" => match arg {
+        Some(arg) => return Ok(Filter::Default(arg.to_owned())),
+        None => return Err("filter 'default' requires a value, as in 'default:foo'".to_owned()),
+      },
+      "urlencode" => Filter::UrlEncode,
+      "base64" => Filter::Base64,
+      "json_escape" => Filter::JsonEscape,
+      "upper" => Filter::Upper,
+      "lower" => Filter::Lower,
+      "string" => Filter::String,
+      _ => return Err(format!("unknown filter '{}'", name)),
+    };
+
+    match arg {
+      Some(_) => Err(format!("filter '{}' takes no value", name)),
+      None => Ok(filter),
+    }
+  }
+
+  // A missing value is only replaced by the default filter.
+  pub fn apply(&self, value: Option<Vec<u8>>) -> Option<Vec<u8>> {
+    match self {
+      Filter::Default(default) => value.or_else(|| Some(default.clone().into_bytes())),
+      _ => value.map(|value| self.transform(&value)),
+    }
+  }
+
+  // Values can be binary, the encoding filters work on their raw bytes and the
+  // others on their text.
+  fn transform(&self, value: &[u8]) -> Vec<u8> {
+    match self {
+      Filter::Default(_) | Filter::String => value.to_vec(),
+      Filter::UrlEncode => percent_encode(value, UNRESERVED).to_string().into_bytes(),
+      Filter::Base64 => base64::engine::general_purpose::STANDARD.encode(value).into_bytes(),
+      Filter::JsonEscape => {
+        let quoted = serde_json::to_string(&String::from_utf8_lossy(value)).unwrap();
+
+        quoted.as_bytes()[1..quoted.len() - 1].to_vec()
+      }
+      Filter::Upper => String::from_utf8_lossy(value).to_uppercase().into_bytes(),
+      Filter::Lower => String::from_utf8_lossy(value).to_lowercase().into_bytes(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn apply(filter: Filter, value: &[u8]) -> String {
+    String::from_utf8(filter.apply(Some(value.to_vec())).unwrap()).unwrap()
+  }
+
+  #[test]
+  fn transforms_values() {
+    assert_eq!(apply(Filter::UrlEncode, b"a b&c=d/~"), "a%20b%26c%3Dd%2F~");
+    assert_eq!(apply(Filter::Base64, b"user:secret"), "dXNlcjpzZWNyZXQ=");
+    assert_eq!(apply(Filter::JsonEscape, b"say \"hi\"\n"), r#"say \"hi\"\n"#);
+    assert_eq!(apply(Filter::Upper, "café".as_bytes()), "CAFÉ");
+    assert_eq!(apply(Filter::Lower, b"MiXeD"), "mixed");
+    assert_eq!(apply(Filter::String, b"42"), "42");
+  }
+
+  #[test]
+  fn encodes_binary_values_as_bytes() {
+    assert_eq!(apply(Filter::Base64, &[0xff, 0x00, 0xfe]), "/wD+");
+    assert_eq!(apply(Filter::UrlEncode, &[0xff, 0x00]), "%FF%00");
+  }
+
+  #[test]
+  fn only_defaults_replace_missing_values() {
+    assert_eq!(Filter::Default("anonymous".to_owned()).apply(None), Some(b"anonymous".to_vec()));
+    assert_eq!(Filter::Default("anonymous".to_owned()).apply(Some(b"alice".to_vec())), Some(b"alice".to_vec()));
+    assert_eq!(Filter::Upper.apply(None), None);
+  }
+
+  #[test]
+  fn parses_filters() {
+    assert_eq!(Filter::parse("default", Some("")), Ok(Filter::Default(String::new())));
+    assert_eq!(Filter::parse("default", None), Err("filter 'default' requires a value, as in 'default:foo'".to_owned()));
+    assert_eq!(Filter::parse("base64", None), Ok(Filter::Base64));
+  }
+}
@@ -0,0 +1,144 @@
+use rand::Rng;
+use serde_json::{Map, Value};
+
+use std::fmt;
+
+use super::{Bindings, Filter, Template};
+use crate::specs::Scenario;
+
+// JSON document whose strings are templates. A string made of a single
+// expression takes the type of its value, so numbers stay numbers.
+#[derive(Debug)]
+pub enum JsonTemplate {
+  Scalar(Value),
+  String(Template),
+  Array(Vec<JsonTemplate>),
+  Object(Vec<(String, JsonTemplate)>),
+}
+
+impl JsonTemplate {
+  pub fn parse(value: &Value) -> Result<JsonTemplate, String> {
+    let template = match value {
+      Value::String(value) => JsonTemplate::String(Template::parse(value)?),
+      Value::Array(values) => JsonTemplate::Array(values.iter().map(JsonTemplate::parse).collect::<Result<_, _>>()?),
+      Value::Object(values) => JsonTemplate::Object(
+        values
+          .iter()
+          .map(|(key, value)| JsonTemplate::parse(value).map(|value| (key.to_owned(), value)))
+          .collect::<Result<_, _>>()?,
+      ),
+      scalar => JsonTemplate::Scalar(scalar.clone()),
+    };
+
+    Ok(template)
+  }
+
+  pub fn render<R: Rng>(&self, scenario: &Scenario, bindings: &mut Bindings, rng: &mut R) -> Result<Value, String> {
+    let value = match self {
+      JsonTemplate::Scalar(value) => value.clone(),
+
+      JsonTemplate::String(template) => {
+        let value = template.render(scenario, bindings, rng)?;
+
+        let typed = template.is_expression() && !template.expressions().any(|expression| expression.filters.contains(&Filter::String));
+
+        match typed {
+          true => match serde_json::from_str(&value) {
+            Ok(typed @ Value::Number(_)) | Ok(typed @ Value::Bool(_)) | Ok(typed @ Value::Null) => typed,
+            _ => Value::String(value),
+          },
+          false => Value::String(value),
+        }
+      }
+
+      JsonTemplate::Array(values) => {
+        let mut array = vec![];
+
+        for value in values {
+          array.push(value.render(scenario, bindings, rng)?);
+        }
+
+        Value::Array(array)
+      }
+
+      JsonTemplate::Object(values) => {
+        let mut object = Map::new();
+
+        for (key, value) in values {
+          object.insert(key.to_owned(), value.render(scenario, bindings, rng)?);
+        }
+
+        Value::Object(object)
+      }
+    };
+
+    Ok(value)
+  }
+
+  fn source(&self) -> Value {
+    match self {
+      JsonTemplate::Scalar(value) => value.clone(),
+      JsonTemplate::String(template) => Value::String(template.to_string()),
+      JsonTemplate::Array(values) => Value::Array(values.iter().map(JsonTemplate::source).collect()),
+      JsonTemplate::Object(values) => Value::Object(values.iter().map(|(key, value)| (key.to_owned(), value.source())).collect()),
+    }
+  }
+}
+
+impl fmt::Display for JsonTemplate {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.source())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use rand::{rngs::StdRng, SeedableRng};
+  use serde_json::json;
+
+  use crate::config::Config;
+
+  // Scenario whose `values` datasource yields its values in order.
+  fn scenario() -> Scenario {
+    Config::scenario("schedulers: []\nupstreams: []\ndatasources:\n  values:\n    kind: array\n    mode: sequential\n    data: [\"42\", \"1.5\", \"true\", \"null\", \"007\", \"abc\", \"[1]\"]\n")
+  }
+
+  fn render(template: Value) -> Value {
+    let template = JsonTemplate::parse(&template).unwrap();
+
+    template.render(&scenario(), &mut Bindings::default(), &mut StdRng::seed_from_u64(0)).unwrap()
+  }
+
+  #[test]
+  fn single_expressions_keep_the_type_of_their_value() {
+    assert_eq!(render(json!("{values}")), json!(42));
+    assert_eq!(render(json!({"a": ["{randint(1,1)}", "{seq}"]})), json!({"a": [1, 1]}));
+  }
+
+  #[test]
+  fn only_numbers_booleans_and_null_are_typed() {
+    let scenario = scenario();
+    let template = JsonTemplate::parse(&json!("{values}")).unwrap();
+    let mut rng = StdRng::seed_from_u64(0);
+
+    let rendered: Vec<Value> = (0..7).map(|_| template.render(&scenario, &mut Bindings::default(), &mut rng).unwrap()).collect();
+
+    assert_eq!(rendered, vec![json!(42), json!(1.5), json!(true), Value::Null, json!("007"), json!("abc"), json!("[1]")]);
+  }
+
+  #[test]
+  fn text_and_string_filter_keep_strings() {
+    assert_eq!(render(json!("{values|string}")), json!("42"));
+    assert_eq!(render(json!("id {values}")), json!("id 42"));
+    assert_eq!(render(json!({"id": "{values}", "label": "#{values}"})), json!({"id": 42, "label": "#42"}));
+  }
+
+  #[test]
+  fn scalars_are_kept() {
+    let template = json!({"count": 3, "enabled": false, "missing": null, "ratio": 0.5, "name": "fixed"});
+
+    assert_eq!(render(template.clone()), template);
+  }
+}
@@ -0,0 +1,396 @@
+mod filter;
+mod json;
+
+pub use self::filter::*;
+pub use self::json::*;
+
+use rand::Rng;
+use regex::{Captures, Regex};
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::datasource::Mode;
+use crate::generator::Generator;
+use crate::specs::Scenario;
+
+// Label of the expressions referring to the cookies of the virtual user, as in
+// `{cookie.session}`.
+pub const COOKIE: &str = "cookie";
+
+// Content of a `{...}` expression: a datasource or generator, an optional
+// column or arguments, then filters.
+const EXPRESSION: &str = r"^(?P<label>[A-Za-z0-9_-]+)(\.(?P<column>[A-Za-z0-9_-]+)|\((?P<args>[^(){}|]*)\))?(?P<filters>(\|[a-z0-9_]+(:[^|{}]*)?)*)$";
+
+#[derive(Debug)]
+pub struct Expression {
+  pub source: String,
+  pub label: String,
+  pub column: Option<String>,
+  pub args: Option<String>,
+  pub filters: Vec<Filter>,
+  generator: Option<Generator>,
+}
+
+impl Expression {
+  fn new(source: &str, capture: &Captures) -> Result<Expression, String> {
+    let label = capture["label"].to_owned();
+    let column = capture.name("column").map(|column| column.as_str().to_owned());
+    let args = capture.name("args").map(|args| args.as_str().to_owned());
+    let mut filters = vec![];
+
+    for filter in capture["filters"].split('|').skip(1) {
+      let filter = match filter.split_once(':') {
+        Some((name, arg)) => Filter::parse(name, Some(arg)),
+        None => Filter::parse(filter, None),
+      };
+
+      filters.push(filter.map_err(|err| format!("invalid expression '{}': {}", source, err))?);
+    }
+
+    Ok(Expression {
+      source: source.to_owned(),
+      generator: Generator::parse(&label, column.as_deref(), args.as_deref()).ok(),
+      label,
+      column,
+      args,
+      filters,
+    })
+  }
+
+  // Identical expressions of a request share the same value, whatever their
+  // filters are.
+  fn key(&self) -> String {
+    match (&self.column, &self.args) {
+      (Some(column), _) => format!("{}.{}", self.label, column),
+      (_, Some(args)) => format!("{}({})", self.label, args),
+      _ => self.label.clone(),
+    }
+  }
+}
+
+#[derive(Debug)]
+enum Segment {
+  Text(String),
+  Expression(Expression),
+}
+
+// Values chosen during a single request, so every expression referring to the
+// same datasource uses the same record, and identical generator expressions
+// yield the same value.
+#[derive(Debug, Clone, Default)]
+pub struct Bindings {
+  rows: HashMap<String, usize>,
+  values: HashMap<String, Option<Vec<u8>>>,
+  // Cookies of the virtual user sending the request, as in a Cookie header
+  cookies: Option<String>,
+}
+
+impl Bindings {
+  pub fn set_cookies(&mut self, cookies: Option<String>) {
+    self.cookies = cookies;
+  }
+
+  fn cookie(&self, name: &str) -> Option<Vec<u8>> {
+    self
+      .cookies
+      .as_ref()?
+      .split("; ")
+      .filter_map(|cookie| cookie.split_once('='))
+      .find(|(key, _)| *key == name)
+      .map(|(_, value)| value.as_bytes().to_vec())
+  }
+}
+
+// String with `{...}` expressions, compiled once when the scenario is created
+// and rendered for every request. `\{` and `\}` are literal braces, and so is
+// any brace which is not part of a valid expression.
+#[derive(Debug)]
+pub struct Template {
+  source: String,
+  segments: Vec<Segment>,
+}
+
+impl Template {
+  pub fn parse(source: &str) -> Result<Template, String> {
+    let rgx = Regex::new(EXPRESSION).unwrap();
+    let mut segments = vec![];
+    let mut text = String::new();
+    let mut idx = 0;
+
+    while idx < source.len() {
+      let rest = &source[idx..];
+
+      if rest.starts_with("\\{") || rest.starts_with("\\}") {
+        text.push_str(&rest[1..2]);
+        idx += 2;
+        continue;
+      }
+
+      if rest.starts_with('{') {
+        if let Some(end) = rest.find('}') {
+          if let Some(capture) = rgx.captures(&rest[1..end]) {
+            if !text.is_empty() {
+              segments.push(Segment::Text(std::mem::take(&mut text)));
+            }
+
+            segments.push(Segment::Expression(Expression::new(&rest[..=end], &capture)?));
+            idx += end + 1;
+            continue;
+          }
+        }
+      }
+
+      let character = rest.chars().next().unwrap();
+      text.push(character);
+      idx += character.len_utf8();
+    }
+
+    if !text.is_empty() {
+      segments.push(Segment::Text(text));
+    }
+
+    Ok(Template { source: source.to_owned(), segments })
+  }
+
+  // Whether the template is made of a single expression, without text.
+  pub fn is_expression(&self) -> bool {
+    matches!(self.segments.as_slice(), [Segment::Expression(_)])
+  }
+
+  pub fn expressions(&self) -> impl Iterator<Item = &Expression> {
+    self.segments.iter().filter_map(|segment| match segment {
+      Segment::Expression(expression) => Some(expression),
+      Segment::Text(_) => None,
+    })
+  }
+
+  // Text between the expressions.
+  pub fn texts(&self) -> impl Iterator<Item = &str> {
+    self.segments.iter().filter_map(|segment| match segment {
+      Segment::Text(text) => Some(text.as_str()),
+      Segment::Expression(_) => None,
+    })
+  }
+
+  pub fn render<R: Rng>(&self, scenario: &Scenario, bindings: &mut Bindings, rng: &mut R) -> Result<String, String> {
+    let bytes = self.render_bytes(scenario, bindings, rng)?;
+
+    Ok(String::from_utf8(bytes).unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).into_owned()))
+  }
+
+  // Replace every expression with its value, failing with the name of the
+  // datasource if one of them is exhausted. Expressions without a value are
+  // left as is. Binary values are kept intact, for bodies.
+  pub fn render_bytes<R: Rng>(&self, scenario: &Scenario, bindings: &mut Bindings, rng: &mut R) -> Result<Vec<u8>, String> {
+    let mut result = vec![];
+
+    for segment in &self.segments {
+      match segment {
+        Segment::Text(text) => result.extend_from_slice(text.as_bytes()),
+
+        Segment::Expression(expression) => {
+          let value = resolve(expression, scenario, bindings, rng)?;
+
+          match expression.filters.iter().fold(value, |value, filter| filter.apply(value)) {
+            Some(value) => result.extend_from_slice(&value),
+            None => result.extend_from_slice(expression.source.as_bytes()),
+          }
+        }
+      }
+    }
+
+    Ok(result)
+  }
+}
+
+impl fmt::Display for Template {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.source)
+  }
+}
+
+// Value of an expression for the current request, from its datasource or, if
+// there is no datasource with that name, from a cookie or its generator.
+fn resolve<R: Rng>(expression: &Expression, scenario: &Scenario, bindings: &mut Bindings, rng: &mut R) -> Result<Option<Vec<u8>>, String> {
+  let label = &expression.label;
+
+  if let (Some(pool), None) = (scenario.datasources.get(label), &expression.args) {
+    let idx = match bindings.rows.get(label) {
+      Some(idx) => *idx,
+      None => match pool.next(rng) {
+        Some(idx) => *bindings.rows.entry(label.to_owned()).or_insert(idx),
+        None if pool.mode == Mode::Once && !pool.is_empty() => return Err(label.to_owned()),
+        None => return Ok(None),
+      },
+    };
+
+    let position = match expression.column {
+      None => Some(0),
+      Some(ref column) => pool.columns.iter().position(|name| name == column),
+    };
+
+    return Ok(position.and_then(|position| pool.get(idx).into_iter().nth(position)));
+  }
+
+  if let (COOKIE, Some(ref name), None) = (label.as_str(), &expression.column, &expression.args) {
+    return Ok(bindings.cookie(name));
+  }
+
+  let key = expression.key();
+
+  if let Some(value) = bindings.values.get(&key) {
+    return Ok(value.clone());
+  }
+
+  let value = expression.generator.as_ref().map(|generator| generator.generate(&scenario.sequence, rng).into_bytes());
+  bindings.values.insert(key, value.clone());
+
+  Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use rand::{rngs::StdRng, SeedableRng};
+
+  use std::env;
+  use std::fs;
+  use std::process;
+
+  use crate::config::Config;
+
+  // Scenario with a `creds` CSV datasource, read in the given mode.
+  fn scenario(name: &str, mode: &str) -> Scenario {
+    let path = env::temp_dir().join(format!("candia-template-{}-{}.csv", process::id(), name));
+
+    fs::write(&path, "username,password\nalice,alice-secret\nbob,bob-secret\ncarol,carol-secret\n").unwrap();
+
+    let scenario = Config::scenario(&format!(
+      "schedulers: []\nupstreams: []\ndatasources:\n  creds:\n    kind: csv\n    source: {}\n    mode: {}\n",
+      path.display(),
+      mode
+    ));
+
+    fs::remove_file(&path).unwrap();
+    scenario
+  }
+
+  fn render(template: &str, scenario: &Scenario, bindings: &mut Bindings, rng: &mut StdRng) -> String {
+    Template::parse(template).unwrap().render(scenario, bindings, rng).unwrap()
+  }
+
+  // Segments of a template, expressions being shown by their key.
+  fn segments(source: &str) -> Vec<String> {
+    Template::parse(source)
+      .unwrap()
+      .segments
+      .iter()
+      .map(|segment| match segment {
+        Segment::Text(text) => format!("text:{}", text),
+        Segment::Expression(expression) => format!("expression:{}", expression.key()),
+      })
+      .collect()
+  }
+
+  #[test]
+  fn parses_text_and_expressions() {
+    assert_eq!(segments("/users/{user}/orders"), vec!["text:/users/", "expression:user", "text:/orders"]);
+    assert_eq!(segments("{creds.username}:{creds.password}"), vec!["expression:creds.username", "text::", "expression:creds.password"]);
+    assert_eq!(segments("{uuid}{int(1,10)}"), vec!["expression:uuid", "expression:int(1,10)"]);
+    assert!(Template::parse("{User-ID_2}").unwrap().is_expression());
+  }
+
+  #[test]
+  fn parses_empty_templates() {
+    assert!(segments("").is_empty());
+    assert!(Template::parse("").unwrap().expressions().next().is_none());
+  }
+
+  #[test]
+  fn escaped_braces_are_text() {
+    assert_eq!(segments(r"\{user\}"), vec!["text:{user}"]);
+    assert_eq!(segments(r#"{"id": \{"user": "{user}"\}}"#), vec![r#"text:{"id": {"user": ""#, "expression:user", r#"text:"}}"#]);
+    // Only braces are escaped, other backslashes are kept
+    assert_eq!(segments(r"\\{user}"), vec![r"text:\{user}"]);
+    assert_eq!(segments(r"a\b{user}"), vec![r"text:a\b", "expression:user"]);
+  }
+
+  #[test]
+  fn invalid_expressions_are_text() {
+    assert_eq!(segments(r#"{"id": 1}"#), vec![r#"text:{"id": 1}"#]);
+    assert_eq!(segments("{user"), vec!["text:{user"]);
+    assert_eq!(segments("user}"), vec!["text:user}"]);
+    assert_eq!(segments("{}"), vec!["text:{}"]);
+    assert_eq!(segments("{ user }"), vec!["text:{ user }"]);
+  }
+
+  #[test]
+  fn parses_filters() {
+    let template = Template::parse("{user|default:anonymous|urlencode}").unwrap();
+    let expression = template.expressions().next().unwrap();
+
+    assert_eq!(expression.label, "user");
+    assert_eq!(expression.filters, vec![Filter::Default("anonymous".to_owned()), Filter::UrlEncode]);
+  }
+
+  #[test]
+  fn rejects_invalid_filters() {
+    assert_eq!(Template::parse("{user|unknown}").unwrap_err(), "invalid expression '{user|unknown}': unknown filter 'unknown'");
+    assert_eq!(Template::parse("{user|upper:x}").unwrap_err(), "invalid expression '{user|upper:x}': filter 'upper' takes no value");
+  }
+
+  #[test]
+  fn keeps_non_ascii_text() {
+    assert_eq!(segments("café/{user}/ü"), vec!["text:café/", "expression:user", "text:/ü"]);
+  }
+
+  #[test]
+  fn columns_of_a_request_come_from_the_same_row() {
+    let scenario = scenario("random", "random");
+    let mut rng = StdRng::seed_from_u64(0);
+
+    for _ in 0..50 {
+      let mut bindings = Bindings::default();
+      let rendered = render("{creds.username}:{creds.password}", &scenario, &mut bindings, &mut rng);
+      let (username, password) = rendered.split_once(':').unwrap();
+
+      assert_eq!(password, format!("{}-secret", username));
+      // Templates rendered later for the same request keep the row
+      assert_eq!(render("{creds.password}", &scenario, &mut bindings, &mut rng), password);
+    }
+  }
+
+  #[test]
+  fn every_request_binds_its_own_row() {
+    let scenario = scenario("sequential", "sequential");
+    let mut rng = StdRng::seed_from_u64(0);
+
+    let users: Vec<String> = (0..4).map(|_| render("{creds.username}/{creds.password}", &scenario, &mut Bindings::default(), &mut rng)).collect();
+
+    assert_eq!(users, vec!["alice/alice-secret", "bob/bob-secret", "carol/carol-secret", "alice/alice-secret"]);
+  }
+
+  #[test]
+  fn unknown_columns_are_left_as_is() {
+    let scenario = scenario("unknown", "sequential");
+    let mut rng = StdRng::seed_from_u64(0);
+
+    assert_eq!(render("{creds.email}|{creds.username}", &scenario, &mut Bindings::default(), &mut rng), "{creds.email}|alice");
+  }
+
+  #[test]
+  fn reads_the_cookies_of_the_virtual_user() {
+    let scenario = Config::scenario("schedulers: []\nupstreams: []\n");
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut bindings = Bindings::default();
+
+    assert_eq!(render("{cookie.session}", &scenario, &mut bindings, &mut rng), "{cookie.session}");
+
+    bindings.set_cookies(Some("theme=dark; session=abc=123".to_owned()));
+
+    assert_eq!(render("{cookie.session}|{cookie.theme}", &scenario, &mut bindings, &mut rng), "abc=123|dark");
+    assert_eq!(render("{cookie.missing|default:none}", &scenario, &mut bindings, &mut rng), "none");
+  }
+}
@@ -1,13 +1,8 @@
-use std::collections::HashMap;
 use std::io::{self, Write};
-use std::process;
 
 use chrono::prelude::*;
 use colored::*;
-use rand::{self, Rng};
-use regex::Regex;
 
-use crate::datasource::Data;
 use crate::result::{Failure, Success};
 
 pub fn current_epoch() -> f64 {
@@ -51,41 +46,29 @@ pub fn print_kv(values: Vec<(&str, String)>) {
   }
 }
 
-pub fn fatal(msg: &str) {
-  eprintln!("{} {}", "FATAL:".red().bold(), msg);
-  process::exit(1);
-}
-
 pub fn info(msg: &str) {
   println!("{} {}", "INFO:".blue().bold(), msg);
 }
 
 pub fn log_header() -> String {
-  "Start offset,Request,State,Status code,Duration (ms)\n".to_owned()
+  "Start offset,Request,State,Status code,Duration (ms),Protocol,Bytes,Reason,Seed\n".to_owned()
 }
 
-pub fn log(result: &Result<Success, Failure>) -> String {
+// Every line carries the seed of the run, so the log alone is enough to replay it.
+pub fn log(result: &Result<Success, Failure>, seed: u64) -> String {
   match result {
-    Ok(s) => format!("{},{},{},{},{}\n", s.start, s.request, "OK", s.code, s.duration),
-    Err(f) => format!("{},{},{},{},{}\n", f.start, f.request, "KO", f.code, f.duration),
+    Ok(s) => format!("{},{},{},{},{},{},{},,{}\n", s.start, s.request, "OK", s.code, s.duration, s.protocol, s.bytes, seed),
+    Err(f) => format!(
+      "{},{},{},{},{},{},{},\"{}\",{}\n",
+      f.start,
+      f.request,
+      "KO",
+      f.code,
+      f.duration,
+      f.protocol,
+      f.bytes,
+      f.reason.replace('"', "\"\""),
+      seed
+    ),
   }
 }
-
-pub fn interpolate(base: &str, datasources: &HashMap<String, Data>) -> String {
-  let rgx = Regex::new(r"\{(?P<label>[a-z_]+)\}").unwrap();
-
-  rgx.captures_iter(base).fold(String::from(base), |result, capture| {
-    let pattern = capture.get(0).unwrap().as_str();
-    let label = capture.name("label").unwrap().as_str();
-
-    let value = match datasources.get(label) {
-      Some(vec) => rand::thread_rng().choose(&vec),
-      None => None,
-    };
-
-    match value {
-      Some(value) => result.replacen(pattern, value, 1),
-      None => result,
-    }
-  })
-}